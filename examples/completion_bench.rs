@@ -0,0 +1,45 @@
+use icli::clap::{Arg, ArgMatches, Command};
+use icli::{Cli, Task, TaskAction};
+use std::time::{Duration, Instant};
+
+struct Numbered(usize);
+
+impl Task for Numbered {
+    fn command(&self) -> Command {
+        (0..10).fold(Command::new(format!("task-{}", self.0)), |c, i| {
+            c.arg(Arg::new(format!("flag-{}", i)).long(format!("flag-{}", i)))
+        })
+    }
+    fn action(&self, _: &(), _: &ArgMatches) -> TaskAction {
+        TaskAction::Continue
+    }
+}
+
+fn cli() -> Cli {
+    (0..200).fold(Cli::new("bench"), |c, i| c.add_task(Numbered(i)))
+}
+
+fn main() {
+    const ROUNDS: u32 = 200;
+    let line = ["task-1"];
+
+    // a fresh Cli for every completion, nothing built yet
+    let mut cold = Duration::ZERO;
+    for _ in 0..ROUNDS {
+        let cli = cli();
+        let start = Instant::now();
+        cli.suggests(&line);
+        cold += start.elapsed();
+    }
+
+    let cli = cli();
+    cli.suggests(&line);
+    let start = Instant::now();
+    for _ in 0..ROUNDS {
+        cli.suggests(&line);
+    }
+    let cached = start.elapsed();
+
+    println!("cold:   {:?} per completion", cold / ROUNDS);
+    println!("cached: {:?} per completion", cached / ROUNDS);
+}
@@ -0,0 +1,21 @@
+use icli::clap::{Arg, ArgAction, Command};
+use icli::{Cli, StatefulTask, TaskAction};
+use std::rc::Rc;
+
+fn main() {
+    let cli = Cli::new("counter").add_task(StatefulTask::new(
+        Command::new("count")
+            .about("count up, the count is kept for the session")
+            .arg(Arg::new("reset").long("reset").action(ArgAction::SetTrue)),
+        0,
+        |count: &mut u32, matches| {
+            match matches.get_flag("reset") {
+                true => *count = 0,
+                false => *count += 1,
+            }
+            println!("{}", count);
+            TaskAction::Continue
+        },
+    ));
+    Rc::new(cli).run_interactive(&()).unwrap();
+}
@@ -0,0 +1,76 @@
+use icli::clap::{Arg, ArgMatches, Command};
+use icli::promkit::{
+    crossterm::event::{KeyCode, KeyEvent, KeyModifiers},
+    grapheme::Graphemes,
+    readline::State,
+    state::Render,
+};
+use icli::{complete, Cli, Task, TaskAction};
+use std::rc::Rc;
+use std::sync::{Arc, Mutex};
+
+type Sessions = Arc<Mutex<Vec<String>>>;
+
+struct Open(Sessions);
+
+impl Task for Open {
+    fn command(&self) -> Command {
+        Command::new("open").about("open a new session")
+    }
+    fn action(&self, _: &(), _: &ArgMatches) -> TaskAction {
+        let mut sessions = self.0.lock().unwrap();
+        let id = format!("session-{}", sessions.len());
+        println!("opened {}", id);
+        sessions.push(id);
+        TaskAction::Continue
+    }
+}
+
+struct Close(Sessions);
+
+impl Task for Close {
+    fn command(&self) -> Command {
+        Command::new("close")
+            .about("close a session")
+            .arg(Arg::new("id").required(true))
+    }
+    fn action(&self, _: &(), matches: &ArgMatches) -> TaskAction {
+        let id = matches.get_one::<String>("id").unwrap();
+        let mut sessions = self.0.lock().unwrap();
+        match sessions.iter().position(|s| s == id) {
+            Some(i) => {
+                sessions.remove(i);
+                println!("closed {}", id);
+                TaskAction::Continue
+            }
+            None => TaskAction::Error(format!("no session {}", id)),
+        }
+    }
+}
+
+fn main() {
+    let sessions = Sessions::default();
+    let ids = sessions.clone();
+    let cli = Cli::new("sessions")
+        .add_task(Open(sessions.clone()))
+        .add_task(Close(sessions))
+        // ids only exist once `open` ran, so they are looked up on every Tab
+        .suggest_provider("close", move |args| match args {
+            [id] => Some(complete(ids.lock().unwrap().clone(), id)),
+            _ => None,
+        })
+        // F5 opens one more session, as if `open` was typed
+        .bind_key(
+            KeyEvent {
+                code: KeyCode::F(5),
+                modifiers: KeyModifiers::NONE,
+            },
+            Box::new(|_, _, out: &mut std::io::Stdout, state: &mut State| {
+                state.0.editor.replace(&Graphemes::from("open"));
+                state.0.next = state.0.editor.clone();
+                state.render(out)?;
+                Ok(true)
+            }),
+        );
+    Rc::new(cli).run_interactive(&()).unwrap();
+}
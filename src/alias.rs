@@ -0,0 +1,50 @@
+use crate::CliError;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+pub(crate) type Aliases = Rc<RefCell<HashMap<String, String>>>;
+
+// the line with its first word replaced for as long as that word is an alias,
+// the words after it stay behind the expansion
+pub(crate) fn expand(line: &str, aliases: &HashMap<String, String>) -> Result<String, CliError> {
+    let mut line = line.trim_start().to_string();
+    let mut seen = vec![];
+    loop {
+        let (word, rest) = line.split_once(char::is_whitespace).unwrap_or((&line, ""));
+        let def = match aliases.get(word) {
+            Some(def) => def,
+            None => return Ok(line),
+        };
+        if seen.iter().any(|s| s == word) {
+            return Err(CliError::AliasLoop(word.to_string()));
+        }
+        seen.push(word.to_string());
+        line = match rest.trim_start() {
+            "" => def.to_string(),
+            rest => format!("{} {}", def, rest),
+        };
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn aliases(defs: &[(&str, &str)]) -> HashMap<String, String> {
+        defs.iter()
+            .map(|(k, v)| (k.to_string(), v.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn the_first_word_expands_until_it_is_no_alias() {
+        let a = aliases(&[("ll", "ls -l"), ("l", "ll -a")]);
+        assert_eq!(expand("  l  x y", &a).unwrap(), "ls -l -a x y");
+        assert_eq!(expand("x ll", &a).unwrap(), "x ll");
+    }
+
+    #[test]
+    fn an_alias_loop_is_an_error() {
+        let a = aliases(&[("a", "b x"), ("b", "a")]);
+        assert!(matches!(expand("a", &a), Err(CliError::AliasLoop(w)) if w == "a"));
+    }
+}
@@ -0,0 +1,388 @@
+use crate::{
+    alias, input, lex, shell, BatchOptions, BatchReport, Cli, CliError, Input, Next, Own, Resolved,
+    Task, TaskAction,
+};
+use clap::{ArgMatches, Command};
+use std::{future::Future, ops::ControlFlow, pin::Pin, rc::Rc, time::Instant};
+
+pub type TaskFuture<'a> = Pin<Box<dyn Future<Output = TaskAction> + 'a>>;
+
+// a task whose action is awaited, it only runs from the `*_async` entry points
+pub trait AsyncTask<C = ()> {
+    fn command(&self) -> Command;
+    fn action<'a>(&'a self, ctx: &'a C, matches: &'a ArgMatches) -> TaskFuture<'a>;
+}
+
+// stands in for an async task in help and completion
+struct Async<C>(Rc<dyn AsyncTask<C>>);
+
+impl<C> Task<C> for Async<C> {
+    fn command(&self) -> Command {
+        self.0.command()
+    }
+    fn action(&self, _: &C, _: &ArgMatches) -> TaskAction {
+        TaskAction::Error(format!(
+            "'{}' can only run from the async entry points",
+            self.0.command().get_name()
+        ))
+    }
+}
+
+impl<C: 'static> Cli<C> {
+    pub fn add_async_task<T: AsyncTask<C> + 'static>(mut self, t: T) -> Self {
+        let t: Rc<dyn AsyncTask<C>> = Rc::new(t);
+        self.async_tasks
+            .insert(t.command().get_name().to_string(), t.clone());
+        self.add_task(Async(t))
+    }
+
+    // like `run`, awaiting async tasks and calling the others as they are; the lines
+    // are taken apart by the same code as the sync entry points use
+    pub async fn run_async(&self, ctx: &C, line: &str) -> Result<TaskAction, CliError> {
+        if let Some(cmd) = self.shell_command(line) {
+            return shell::run(cmd);
+        }
+        let expanded = &alias::expand(line, &self.aliases.borrow())?;
+        match self.resolve(line, expanded) {
+            Resolved::Done(r) => r,
+            Resolved::Repeat(count, cmd) => self.repeat_async(ctx, count, cmd).await,
+            Resolved::Args(args, raw) => {
+                let r = match self.parse_or_help(args) {
+                    Ok(Some(matches)) => Ok(self.action_async(ctx, &matches, raw).await),
+                    Ok(None) => Ok(TaskAction::Continue),
+                    Err(e) => Err(e),
+                };
+                self.or_fallback(line, r)
+            }
+        }
+    }
+
+    // `action_raw`, with the async tasks awaited
+    async fn action_async(&self, ctx: &C, matches: &ArgMatches, raw: Option<&str>) -> TaskAction {
+        let (name, matches) = matches.subcommand().unwrap();
+        let started = match self.start_task(name, matches) {
+            Ok(started) => started,
+            Err(action) => return action,
+        };
+        let action = match (self.own(name, matches), self.async_tasks.get(name)) {
+            (Some(Own::Source), _) => Box::pin(self.source_async(ctx, matches)).await,
+            (Some(Own::Repeat(count, cmd)), _) => Box::pin(self.repeat_async(ctx, count, &cmd))
+                .await
+                .unwrap_or_else(|e| TaskAction::Error(e.to_string())),
+            (None, Some(t)) => t.action(ctx, matches).await,
+            (None, None) => self.cmds[name].action_raw(ctx, matches, raw),
+        };
+        self.end_task(name, started, action)
+    }
+
+    async fn repeat_async(&self, ctx: &C, count: usize, cmd: &str) -> Result<TaskAction, CliError> {
+        for i in 0..count {
+            let r = Box::pin(self.run_async(ctx, cmd)).await;
+            if let Some(action) = self.repeat_stops(i, count, r) {
+                return Ok(action);
+            }
+        }
+        Ok(TaskAction::Continue)
+    }
+
+    async fn source_async(&self, ctx: &C, matches: &ArgMatches) -> TaskAction {
+        let (path, f, options) = match self.source_file(matches) {
+            Ok(file) => file,
+            Err(action) => return action,
+        };
+        self.source_depth.set(self.source_depth.get() + 1);
+        let r = self
+            .run_lines_async(ctx, Self::file_lines(path, f), options)
+            .await;
+        self.source_depth.set(self.source_depth.get() - 1);
+        Self::sourced(path, r)
+    }
+
+    async fn run_chain_async(&self, ctx: &C, line: &str) -> Result<TaskAction, CliError> {
+        self.record_command(line);
+        let mut last = Ok(TaskAction::Continue);
+        for (op, cmd) in self.split_chain(line)? {
+            if self.chain_skips(op, &last) {
+                continue;
+            }
+            last = self.run_async(ctx, cmd).await;
+            if self.chain_ends(&mut last) {
+                break;
+            }
+        }
+        last
+    }
+
+    async fn run_line_async(&self, ctx: &C, line: &str) -> Result<TaskAction, CliError> {
+        let mut commands = lex::split_unquoted(line, ';', |c| self.claims(c))
+            .into_iter()
+            .peekable();
+        while let Some(cmd) = commands.next() {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let r = self.run_chain_async(ctx, cmd).await;
+            if let Some(r) = self.line_ends(r, commands.peek().is_some()) {
+                return r;
+            }
+        }
+        Ok(TaskAction::Continue)
+    }
+
+    // `run_batch` for scripts with async tasks
+    pub async fn run_batch_async(&self, ctx: &C, cmd: &str) -> Result<TaskAction, CliError> {
+        self.run_batch_with_async(ctx, cmd, BatchOptions::default())
+            .await
+            .map(|r| r.action)
+    }
+
+    pub async fn run_batch_with_async(
+        &self,
+        ctx: &C,
+        cmd: &str,
+        options: BatchOptions,
+    ) -> Result<BatchReport, CliError> {
+        self.run_lines_async(ctx, cmd.split('\n').map(Ok), options)
+            .await
+    }
+
+    async fn run_lines_async<I: Iterator<Item = Result<S, CliError>>, S: AsRef<str>>(
+        &self,
+        ctx: &C,
+        lines: I,
+        options: BatchOptions,
+    ) -> Result<BatchReport, CliError> {
+        let _unattended = input::Unattended::start(self.auto_confirm);
+        let _watch = self.cancel.watch();
+        let mut report = BatchReport::default();
+        for command in self.commands(lines, options.join_quotes) {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let over = match self.next_command(command, &options)? {
+                Next::Run(line, text) => {
+                    let start = Instant::now();
+                    let r = self.run_chain_async(ctx, &text).await;
+                    self.batch_ran(&mut report, &options, line, text, r, Some(start))
+                }
+                Next::Broken(line, e) => {
+                    self.batch_ran(&mut report, &options, line, String::new(), Err(e), None)
+                }
+            };
+            if let ControlFlow::Break(r) = over {
+                r?;
+                break;
+            }
+        }
+        Ok(self.batch_done(report, &options))
+    }
+
+    async fn run_input_async(&self, ctx: &C, line: &str) -> Result<TaskAction, CliError> {
+        match line.contains('\n') {
+            true => self
+                .run_batch_with_async(ctx, line, BatchOptions::default().join_quotes(true))
+                .await
+                .map(|r| r.action),
+            false => self.run_line_async(ctx, line).await,
+        }
+    }
+
+    async fn start_rc_async(&self, ctx: &C) -> Result<Option<TaskAction>, CliError> {
+        let r = match self.open_rc() {
+            Ok(Some((path, f, options))) => self
+                .run_lines_async(ctx, Self::file_lines(&path, f), options)
+                .await
+                .map(|r| r.action),
+            Ok(None) => Ok(TaskAction::Continue),
+            Err(e) => Err(e),
+        };
+        self.rc_done(r)
+    }
+
+    async fn plain_loop_async(&self, ctx: &C) -> Result<TaskAction, CliError> {
+        if let Some(action) = self.start_rc_async(ctx).await? {
+            return Ok(action);
+        }
+        let _unattended = input::Unattended::start(self.auto_confirm);
+        for text in self.plain_lines() {
+            let r = {
+                let _watch = self.cancel.watch();
+                match text {
+                    Ok(text) => self.run_line_async(ctx, &text).await,
+                    Err(e) => Err(e),
+                }
+            };
+            if let Some(action) = self.line_done(r) {
+                return Ok(action);
+            }
+        }
+        Ok(TaskAction::Exit(0))
+    }
+
+    // reading the prompt still blocks the executor thread it runs on
+    pub async fn run_interactive_async(self: &Rc<Self>, ctx: &C) -> Result<TaskAction, CliError> {
+        if !self.prompting() {
+            return self.plain_loop_async(ctx).await;
+        }
+        self.load_history();
+        let r = match self.start_rc_async(ctx).await? {
+            Some(action) => Ok(action),
+            None => {
+                self.prompt_loop_async(ctx, &mut self.start_interactive(|b| b)?)
+                    .await
+            }
+        };
+        self.end_interactive(r)
+    }
+
+    async fn prompt_loop_async(
+        &self,
+        ctx: &C,
+        prompt: &mut promkit::Prompt<promkit::readline::State>,
+    ) -> Result<TaskAction, CliError> {
+        loop {
+            let line = match self.read_line(prompt)? {
+                Input::Line(line) => line,
+                Input::Skip => continue,
+                Input::Eof => break Ok(TaskAction::Exit(0)),
+                Input::Action(action) => match self.line_done(Ok(action)) {
+                    Some(action) => break Ok(action),
+                    None => continue,
+                },
+            };
+            let r = {
+                let _watch = self.cancel.watch();
+                self.run_input_async(ctx, &line).await
+            };
+            if let Some(action) = self.line_done(r) {
+                break Ok(action);
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::ErrorPolicy;
+    use clap::Arg;
+    use std::{
+        cell::RefCell,
+        sync::{Arc, Mutex},
+        task::{Context, Poll, Waker},
+    };
+
+    // enough of an executor for tasks that only ever wait on `yielded`
+    fn block_on<F: Future>(f: F) -> F::Output {
+        let mut f = std::pin::pin!(f);
+        let mut cx = Context::from_waker(Waker::noop());
+        loop {
+            if let Poll::Ready(v) = f.as_mut().poll(&mut cx) {
+                return v;
+            }
+        }
+    }
+
+    // pending once, as a task waiting on the network would be
+    async fn yielded() {
+        let mut once = false;
+        std::future::poll_fn(|_| match std::mem::replace(&mut once, true) {
+            true => Poll::Ready(()),
+            false => Poll::Pending,
+        })
+        .await
+    }
+
+    type Words = RefCell<Vec<String>>;
+
+    // `get NAME` writes its name down, `get fail` fails
+    struct Get;
+
+    impl AsyncTask<Words> for Get {
+        fn command(&self) -> Command {
+            Command::new("get").arg(Arg::new("name").required(true))
+        }
+        fn action<'a>(&'a self, words: &'a Words, matches: &'a ArgMatches) -> TaskFuture<'a> {
+            Box::pin(async move {
+                yielded().await;
+                let name = matches.get_one::<String>("name").unwrap();
+                if name == "fail" {
+                    return TaskAction::Error("no such thing".into());
+                }
+                words.borrow_mut().push(name.clone());
+                TaskAction::Continue
+            })
+        }
+    }
+
+    fn getting() -> Cli<Words> {
+        Cli::for_context("test").add_async_task(Get)
+    }
+
+    #[test]
+    fn chains_await_their_tasks() {
+        let words = RefCell::new(vec![]);
+        let r = block_on(getting().run_batch_async(&words, "get a && get fail && get b || get c"));
+        assert_eq!(r.unwrap(), TaskAction::Continue);
+        assert_eq!(*words.borrow(), ["a", "c"]);
+        let r = block_on(getting().run_batch_async(&words, "get d; get e && get fail"));
+        assert!(matches!(r, Err(CliError::Batch { line: 1, .. })), "{:?}", r);
+        assert_eq!(*words.borrow(), ["a", "c", "d", "e"]);
+    }
+
+    #[test]
+    fn hooks_wrap_async_tasks() {
+        let ran = Arc::new(Mutex::new(vec![]));
+        let seen = ran.clone();
+        let cli = getting()
+            .on_before(|_, m| match m.get_one::<String>("name").unwrap().as_str() {
+                "secret" => Err("not allowed".into()),
+                _ => Ok(()),
+            })
+            .on_after(move |name, action, _| {
+                seen.lock().unwrap().push(format!("{} {:?}", name, action));
+            });
+        let words = RefCell::new(vec![]);
+        block_on(cli.run_async(&words, "get a")).unwrap();
+        let refused = block_on(cli.run_async(&words, "get secret")).unwrap();
+        assert_eq!(refused, TaskAction::Error("not allowed".into()));
+        assert_eq!(*words.borrow(), ["a"]);
+        assert_eq!(*ran.lock().unwrap(), ["get Continue"]);
+    }
+
+    #[test]
+    fn repeat_and_source_run_async_tasks() {
+        let cli = getting().allow_repeat(true).allow_source(true);
+        let words = RefCell::new(vec![]);
+        block_on(cli.run_async(&words, "repeat 3 get x")).unwrap();
+        assert_eq!(*words.borrow(), ["x", "x", "x"]);
+        // a count that is only known once the line is parsed
+        let cli = cli.variables(true);
+        block_on(cli.run_batch_async(&words, "set N=2\nrepeat $N get y")).unwrap();
+        assert_eq!(words.borrow()[3..], ["y", "y"]);
+        let path = std::env::temp_dir().join(format!("icli-async-{}.sh", std::process::id()));
+        std::fs::write(&path, "get s1\nget s2\n").unwrap();
+        let line = format!("source '{}'", path.display());
+        let r = block_on(cli.run_async(&words, &line));
+        let _ = std::fs::remove_file(&path);
+        assert_eq!(r.unwrap(), TaskAction::Continue);
+        assert_eq!(words.borrow()[5..], ["s1", "s2"]);
+    }
+
+    #[test]
+    fn batch_options_reach_async_batches() {
+        let script = "get a\nget fail\nget b";
+        let words = RefCell::new(vec![]);
+        let r = block_on(getting().run_batch_async(&words, script));
+        assert!(matches!(r, Err(CliError::Batch { line: 2, .. })), "{:?}", r);
+        assert_eq!(*words.borrow(), ["a"]);
+        let options = BatchOptions::default()
+            .on_error(ErrorPolicy::Collect)
+            .timed(true);
+        let report = block_on(getting().run_batch_with_async(&words, script, options)).unwrap();
+        assert_eq!(*words.borrow(), ["a", "a", "b"]);
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0, 2);
+        assert_eq!(report.lines.len(), 3);
+    }
+}
@@ -0,0 +1,376 @@
+use crate::{
+    alias::Aliases,
+    history::SharedHistory,
+    jobs::{self, SharedJobs},
+    vars,
+    vars::Variables,
+    CancelToken, CliError, Task, TaskAction,
+};
+use clap::{value_parser, Arg, ArgAction, ArgMatches, Command, ValueHint};
+use promkit::termutil;
+use std::{io, path::PathBuf, time::Duration};
+
+fn assignment(s: &str) -> Result<(String, String), String> {
+    match s.split_once('=') {
+        Some((name, value)) if vars::is_name(name) => Ok((name.to_string(), value.to_string())),
+        _ => Err(format!("expected NAME=VALUE, got '{}'", s)),
+    }
+}
+
+pub(crate) struct Set(pub(crate) Variables);
+
+impl<C> Task<C> for Set {
+    fn command(&self) -> Command {
+        Command::new("set")
+            .about("Set variables, or list them all without arguments")
+            .arg(
+                Arg::new("assignments")
+                    .value_name("NAME=VALUE")
+                    .num_args(0..)
+                    .value_parser(assignment),
+            )
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        let mut vars = self.0.borrow_mut();
+        match matches.get_many::<(String, String)>("assignments") {
+            Some(assignments) => vars.extend(assignments.cloned()),
+            None => {
+                let mut all = vars.iter().collect::<Vec<_>>();
+                all.sort();
+                for (name, value) in all {
+                    println!("{}={}", name, value);
+                }
+            }
+        }
+        TaskAction::Continue
+    }
+}
+
+pub(crate) struct Unset(pub(crate) Variables);
+
+impl<C> Task<C> for Unset {
+    fn command(&self) -> Command {
+        Command::new("unset").about("Remove variables").arg(
+            Arg::new("names")
+                .value_name("NAME")
+                .num_args(1..)
+                .required(true),
+        )
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        let mut vars = self.0.borrow_mut();
+        for name in matches.get_many::<String>("names").into_iter().flatten() {
+            vars.remove(name);
+        }
+        TaskAction::Continue
+    }
+}
+
+pub(crate) struct Alias(pub(crate) Aliases);
+
+impl<C> Task<C> for Alias {
+    fn command(&self) -> Command {
+        Command::new("alias")
+            .about("Define a shortcut, or list them without arguments")
+            .arg(Arg::new("name"))
+            .arg(
+                Arg::new("expansion")
+                    .num_args(1..)
+                    .allow_hyphen_values(true),
+            )
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        let mut aliases = self.0.borrow_mut();
+        let name = matches.get_one::<String>("name");
+        match (name, matches.get_many::<String>("expansion")) {
+            (Some(name), Some(words)) => {
+                // a single word is taken as the line itself, `alias st "status --all"`
+                let words = words.map(|w| w.as_str()).collect::<Vec<_>>();
+                let def = match words[..] {
+                    [line] => line.to_string(),
+                    _ => words
+                        .iter()
+                        .map(|w| quoted(w))
+                        .collect::<Vec<_>>()
+                        .join(" "),
+                };
+                aliases.insert(name.to_string(), def);
+            }
+            (Some(name), None) => match aliases.get(name) {
+                Some(def) => println!("alias {} {}", name, quoted(def)),
+                None => return TaskAction::Error(format!("no alias '{}'", name)),
+            },
+            _ => {
+                let mut all = aliases.iter().collect::<Vec<_>>();
+                all.sort();
+                for (name, def) in all {
+                    println!("alias {} {}", name, quoted(def));
+                }
+            }
+        }
+        TaskAction::Continue
+    }
+}
+
+fn quoted(s: &str) -> String {
+    shlex::try_quote(s).map_or(s.to_string(), |q| q.to_string())
+}
+
+pub(crate) struct Unalias(pub(crate) Aliases);
+
+impl<C> Task<C> for Unalias {
+    fn command(&self) -> Command {
+        Command::new("unalias").about("Remove shortcuts").arg(
+            Arg::new("names")
+                .value_name("NAME")
+                .num_args(1..)
+                .required(true),
+        )
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        let mut aliases = self.0.borrow_mut();
+        for name in matches.get_many::<String>("names").into_iter().flatten() {
+            if aliases.remove(name).is_none() {
+                return TaskAction::Error(format!("no alias '{}'", name));
+            }
+        }
+        TaskAction::Continue
+    }
+}
+
+// the Cli runs the script itself, this only describes the command
+pub(crate) struct Source;
+
+impl<C> Task<C> for Source {
+    fn command(&self) -> Command {
+        Command::new("source")
+            .about("Run the commands of a script file")
+            .arg(
+                Arg::new("path")
+                    .required(true)
+                    .value_hint(ValueHint::FilePath)
+                    .value_parser(value_parser!(PathBuf)),
+            )
+            .arg(
+                Arg::new("ignore-errors")
+                    .long("ignore-errors")
+                    .action(ArgAction::SetTrue)
+                    .help("Go on after a failing command"),
+            )
+    }
+    fn action(&self, _: &C, _: &ArgMatches) -> TaskAction {
+        TaskAction::Error("source is run by the cli".into())
+    }
+}
+
+// like `Source`, the Cli runs the repeated command itself
+pub(crate) struct Repeat;
+
+impl<C> Task<C> for Repeat {
+    fn command(&self) -> Command {
+        Command::new("repeat")
+            .about("Run a command a number of times")
+            .arg(
+                Arg::new("count")
+                    .required(true)
+                    .value_parser(value_parser!(usize)),
+            )
+            .arg(
+                Arg::new("command")
+                    .required(true)
+                    .num_args(1..)
+                    .trailing_var_arg(true)
+                    .allow_hyphen_values(true),
+            )
+    }
+    fn action(&self, _: &C, _: &ArgMatches) -> TaskAction {
+        TaskAction::Error("repeat is run by the cli".into())
+    }
+}
+
+// `500ms`, `2s`, `1m30s`, `1.5h`; a bare number is seconds
+pub(crate) fn duration(s: &str) -> Result<Duration, String> {
+    let invalid = || format!("expected a duration like 500ms, 2s or 1m, got '{}'", s);
+    let mut rest = s.trim();
+    if rest.is_empty() {
+        return Err(invalid());
+    }
+    let mut total = 0.0;
+    while !rest.is_empty() {
+        let digits = rest
+            .find(|c: char| !c.is_ascii_digit() && c != '.')
+            .unwrap_or(rest.len());
+        let n = rest[..digits].parse::<f64>().map_err(|_| invalid())?;
+        rest = &rest[digits..];
+        let unit = rest
+            .find(|c: char| !c.is_ascii_alphabetic())
+            .unwrap_or(rest.len());
+        total += n * match &rest[..unit] {
+            "ms" => 0.001,
+            "" | "s" => 1.0,
+            "m" => 60.0,
+            "h" => 3600.0,
+            _ => return Err(invalid()),
+        };
+        rest = &rest[unit..];
+    }
+    Duration::try_from_secs_f64(total).map_err(|_| invalid())
+}
+
+// Ctrl+C at the prompt cuts it short
+pub(crate) struct Sleep(pub(crate) CancelToken);
+
+impl<C> Task<C> for Sleep {
+    fn command(&self) -> Command {
+        Command::new("sleep")
+            .about("Wait for a while")
+            .arg(Arg::new("duration").required(true).value_parser(duration))
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        self.0
+            .sleep(*matches.get_one::<Duration>("duration").unwrap());
+        TaskAction::Continue
+    }
+}
+
+pub(crate) struct Jobs(pub(crate) SharedJobs);
+
+impl<C> Task<C> for Jobs {
+    fn command(&self) -> Command {
+        Command::new("jobs").about("List the commands running in the background")
+    }
+    fn action(&self, _: &C, _: &ArgMatches) -> TaskAction {
+        for (id, line, done) in self.0.borrow().list() {
+            let state = match done {
+                true => "done",
+                false => "running",
+            };
+            println!("[{}] {:<8} {}", id, state, line);
+        }
+        TaskAction::Continue
+    }
+}
+
+pub(crate) struct Wait(pub(crate) SharedJobs);
+
+impl<C> Task<C> for Wait {
+    fn command(&self) -> Command {
+        Command::new("wait")
+            .about("Wait for background commands, all of them without an id")
+            .arg(
+                Arg::new("ids")
+                    .value_name("ID")
+                    .num_args(0..)
+                    .value_parser(value_parser!(usize)),
+            )
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        let ids = match matches.get_many::<usize>("ids") {
+            Some(ids) => ids.copied().collect(),
+            None => self.0.borrow().ids(),
+        };
+        let mut failed = None;
+        for id in ids {
+            match self.0.borrow_mut().wait(id) {
+                Some(ended) => {
+                    println!("{}", jobs::report(&ended));
+                    if matches!(ended, (_, _, Some(TaskAction::Error(_)) | None)) {
+                        failed = Some(format!("job {} failed", id));
+                    }
+                }
+                None => failed = Some(format!("no job {}", id)),
+            }
+        }
+        failed.map_or(TaskAction::Continue, TaskAction::Error)
+    }
+}
+
+pub(crate) struct History(pub(crate) SharedHistory);
+
+impl<C> Task<C> for History {
+    fn command(&self) -> Command {
+        Command::new("history")
+            .about("List the lines entered, numbered the way `!N` counts them")
+            .arg(
+                Arg::new("n")
+                    .short('n')
+                    .value_name("COUNT")
+                    .help("Only the last COUNT lines")
+                    .value_parser(value_parser!(usize)),
+            )
+            .arg(
+                Arg::new("clear")
+                    .long("clear")
+                    .help("Forget all lines, in the history file too")
+                    .action(ArgAction::SetTrue)
+                    .conflicts_with("n"),
+            )
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        if matches.get_flag("clear") {
+            return match self.0.borrow_mut().clear() {
+                Err(CliError::File { path, source }) => {
+                    TaskAction::Error(format!("{}: {}", path.display(), source))
+                }
+                _ => TaskAction::Continue,
+            };
+        }
+        let history = self.0.borrow();
+        let skip = matches
+            .get_one::<usize>("n")
+            .map_or(0, |n| history.entries.len().saturating_sub(*n));
+        for (i, line) in history.entries.iter().enumerate().skip(skip) {
+            println!("{:>5}  {}", i + 1, line);
+        }
+        TaskAction::Continue
+    }
+}
+
+pub(crate) struct Clear;
+
+impl<C> Task<C> for Clear {
+    fn command(&self) -> Command {
+        Command::new("clear").about("Clear the screen")
+    }
+    fn action(&self, _: &C, _: &ArgMatches) -> TaskAction {
+        match termutil::clear(&mut io::stdout()) {
+            Ok(()) => TaskAction::Continue,
+            Err(e) => TaskAction::Error(e.to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn durations_parse() {
+        let ms = Duration::from_millis;
+        assert_eq!(duration("500ms"), Ok(ms(500)));
+        assert_eq!(duration("2s"), Ok(ms(2000)));
+        assert_eq!(duration("2"), Ok(ms(2000)));
+        assert_eq!(duration("1m"), Ok(ms(60_000)));
+        assert_eq!(duration("1m30s"), Ok(ms(90_000)));
+        assert_eq!(duration("1.5h"), Ok(ms(5_400_000)));
+        assert_eq!(duration(" 0 "), Ok(ms(0)));
+    }
+
+    #[test]
+    fn bad_durations_are_rejected() {
+        for s in ["", "-1s", "abc", "5x", "1..2s", "ms", "s2", "1e3"] {
+            assert!(duration(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn sleep_gives_way_to_a_cancel() {
+        let token = CancelToken::default();
+        token.cancel();
+        let start = std::time::Instant::now();
+        let sleep = Sleep(token);
+        let matches = Task::<()>::command(&sleep).get_matches_from(["sleep", "10s"]);
+        assert_eq!(sleep.action(&(), &matches), TaskAction::Continue);
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+}
@@ -0,0 +1,152 @@
+use std::{
+    sync::{
+        atomic::{AtomicBool, AtomicUsize, Ordering},
+        Arc, OnceLock,
+    },
+    time::{Duration, Instant},
+};
+
+// set when Ctrl+C is pressed while a command runs at the prompt, long running
+// tasks poll it to give up early
+#[derive(Debug, Clone, Default)]
+pub struct CancelToken {
+    cancelled: Arc<AtomicBool>,
+    // how many watches are up on it
+    watched: Arc<AtomicUsize>,
+}
+
+// the one SIGINT handler of the process; signal-hook leaves a signal ignored once its
+// last handler is unregistered, so it stays and does the default while nothing watches
+struct Signals {
+    // nothing watches, the signal ends the process as usual
+    idle: Arc<AtomicBool>,
+    watches: AtomicUsize,
+    // a signal came since the last watch went up
+    hit: Arc<AtomicBool>,
+}
+
+fn signals() -> &'static Signals {
+    static SIGNALS: OnceLock<Signals> = OnceLock::new();
+    SIGNALS.get_or_init(|| {
+        let s = Signals {
+            idle: Arc::new(AtomicBool::new(true)),
+            watches: AtomicUsize::new(0),
+            hit: Arc::new(AtomicBool::new(false)),
+        };
+        #[cfg(windows)]
+        let signals = [signal_hook::consts::SIGINT, signal_hook::consts::SIGBREAK];
+        #[cfg(not(windows))]
+        let signals = [signal_hook::consts::SIGINT];
+        for sig in signals {
+            let _ = signal_hook::flag::register_conditional_default(sig, s.idle.clone());
+            let _ = signal_hook::flag::register(sig, s.hit.clone());
+        }
+        s
+    })
+}
+
+impl CancelToken {
+    pub fn is_cancelled(&self) -> bool {
+        self.cancelled.load(Ordering::SeqCst)
+            || self.watched.load(Ordering::SeqCst) > 0 && signals().hit.load(Ordering::SeqCst)
+    }
+
+    pub fn cancel(&self) {
+        self.cancelled.store(true, Ordering::SeqCst)
+    }
+
+    pub(crate) fn reset(&self) {
+        self.cancelled.store(false, Ordering::SeqCst)
+    }
+
+    // false if cancelled before `d` is over
+    pub fn sleep(&self, d: Duration) -> bool {
+        let end = Instant::now() + d;
+        while let Some(left) = end.checked_duration_since(Instant::now()) {
+            if self.is_cancelled() {
+                return false;
+            }
+            std::thread::sleep(left.min(Duration::from_millis(20)));
+        }
+        !self.is_cancelled()
+    }
+
+    // SIGINT cancels instead of ending the process until the guard is dropped, and so
+    // does Ctrl+Break on Windows
+    pub(crate) fn watch(&self) -> Watch {
+        self.reset();
+        let s = signals();
+        if s.watches.fetch_add(1, Ordering::SeqCst) == 0 {
+            s.hit.store(false, Ordering::SeqCst);
+            s.idle.store(false, Ordering::SeqCst);
+        }
+        self.watched.fetch_add(1, Ordering::SeqCst);
+        Watch(self.clone())
+    }
+}
+
+pub(crate) struct Watch(CancelToken);
+
+impl Drop for Watch {
+    fn drop(&mut self) {
+        // kept on the token for whoever looks after the command
+        if self.0.is_cancelled() {
+            self.0.cancel();
+        }
+        self.0.watched.fetch_sub(1, Ordering::SeqCst);
+        let s = signals();
+        if s.watches.fetch_sub(1, Ordering::SeqCst) == 1 {
+            s.idle.store(true, Ordering::SeqCst);
+        }
+    }
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+    use signal_hook::{consts::SIGINT, low_level::raise};
+    use std::{
+        env,
+        os::unix::process::ExitStatusExt,
+        process::{Command, Stdio},
+    };
+
+    const CHILD: &str = "ICLI_SIGINT_CHILD";
+
+    // run in a process of its own by the test below, the signals would reach every
+    // other test's watch
+    #[test]
+    fn watch_in_a_child() {
+        if env::var_os(CHILD).is_none() {
+            return;
+        }
+        let token = CancelToken::default();
+        for _ in 0..2 {
+            let watch = token.watch();
+            raise(SIGINT).unwrap();
+            assert!(token.is_cancelled());
+            drop(watch);
+            // still cancelled for whoever looks after the command
+            assert!(token.is_cancelled());
+        }
+        // nothing watches, the process ends here
+        raise(SIGINT).unwrap();
+        std::thread::sleep(Duration::from_secs(1));
+        std::process::exit(0);
+    }
+
+    #[test]
+    fn signal_ends_the_process_again_after_a_watch() {
+        let status = Command::new(env::current_exe().unwrap())
+            .args([
+                "cancel::tests::watch_in_a_child",
+                "--exact",
+                "--test-threads=1",
+            ])
+            .env(CHILD, "1")
+            .stdout(Stdio::null())
+            .status()
+            .unwrap();
+        assert_eq!(status.signal(), Some(SIGINT), "{:?}", status);
+    }
+}
@@ -0,0 +1,31 @@
+use promkit::grapheme::Graphemes;
+
+// one line the completion could turn into, with what it stands for if known
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Candidate {
+    pub text: Graphemes,
+    pub description: Option<String>,
+}
+
+impl From<Graphemes> for Candidate {
+    fn from(text: Graphemes) -> Self {
+        Candidate {
+            text,
+            description: None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Completions {
+    // the line as far as it can be completed unambiguously
+    pub replacement: Graphemes,
+    pub candidates: Vec<Candidate>,
+}
+
+impl Completions {
+    // what `suggests` used to give: just the completed line
+    pub fn into_replacement(self) -> Graphemes {
+        self.replacement
+    }
+}
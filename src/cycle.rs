@@ -0,0 +1,110 @@
+use crate::lex;
+
+// menu-completion state shared by the completion key handlers
+#[derive(Debug, Default)]
+pub(crate) struct Cycle {
+    original: String,
+    candidates: Vec<String>,
+    index: usize,
+}
+
+impl Cycle {
+    fn current(&self) -> Option<&str> {
+        self.candidates.get(self.index).map(|s| s.as_str())
+    }
+
+    // a session only stays alive while the buffer still shows our last pick
+    pub(crate) fn is_active(&self, line: &str) -> bool {
+        self.current() == Some(line)
+    }
+
+    // the token each candidate offers, without the part of the line they all share
+    pub(crate) fn listing(&self) -> Vec<String> {
+        self.candidates
+            .iter()
+            .filter_map(|c| lex::split_partial(c).pop())
+            .collect()
+    }
+
+    pub(crate) fn start(&mut self, original: &str, candidates: Vec<String>) -> Option<&str> {
+        self.original = original.to_string();
+        self.candidates = candidates;
+        self.index = 0;
+        self.current()
+    }
+
+    pub(crate) fn step(&mut self, forward: bool) -> Option<&str> {
+        let len = self.candidates.len();
+        if len == 0 {
+            return None;
+        }
+        self.index = if forward {
+            (self.index + 1) % len
+        } else {
+            (self.index + len - 1) % len
+        };
+        self.current()
+    }
+
+    pub(crate) fn cancel(&mut self, line: &str) -> Option<String> {
+        let original = if self.is_active(line) {
+            Some(std::mem::take(&mut self.original))
+        } else {
+            None
+        };
+        self.reset();
+        original
+    }
+
+    pub(crate) fn reset(&mut self) {
+        *self = Self::default();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn cycle() -> Cycle {
+        let mut c = Cycle::default();
+        let candidates = ["start", "status", "stop"].map(String::from).to_vec();
+        assert_eq!(c.start("st", candidates), Some("start"));
+        c
+    }
+
+    #[test]
+    fn steps_wrap_around() {
+        let mut c = cycle();
+        assert_eq!(c.step(true), Some("status"));
+        assert_eq!(c.step(true), Some("stop"));
+        assert_eq!(c.step(true), Some("start"));
+        assert_eq!(c.step(false), Some("stop"));
+        assert_eq!(Cycle::default().step(true), None);
+    }
+
+    #[test]
+    fn typing_ends_the_session() {
+        let c = cycle();
+        assert!(c.is_active("start"));
+        assert!(!c.is_active("starts"));
+    }
+
+    #[test]
+    fn cancel_gives_back_the_original() {
+        let mut c = cycle();
+        c.step(true);
+        assert_eq!(c.cancel("status").as_deref(), Some("st"));
+        assert!(!c.is_active("status"));
+        // nothing to give back once the line moved on
+        let mut c = cycle();
+        assert_eq!(c.cancel("start x"), None);
+    }
+
+    #[test]
+    fn listing_shows_the_last_token() {
+        let mut c = Cycle::default();
+        let candidates = ["run --fast", "run --slow"].map(String::from).to_vec();
+        c.start("run --", candidates);
+        assert_eq!(c.listing(), ["--fast", "--slow"]);
+    }
+}
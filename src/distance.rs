@@ -0,0 +1,50 @@
+// edit distance between two names, counted in chars
+pub(crate) fn levenshtein(a: &str, b: &str) -> usize {
+    let b = b.chars().collect::<Vec<_>>();
+    let mut row = (0..=b.len()).collect::<Vec<_>>();
+    for (i, ca) in a.chars().enumerate() {
+        let mut diag = row[0];
+        row[0] = i + 1;
+        for (j, cb) in b.iter().enumerate() {
+            let up = row[j + 1];
+            row[j + 1] = (diag + (ca != *cb) as usize).min(row[j] + 1).min(up + 1);
+            diag = up;
+        }
+    }
+    row[b.len()]
+}
+
+// the closest names to a typo, best first; far-off names are never offered
+pub(crate) fn closest<'a>(name: &str, names: &'a [String], max: usize) -> Vec<&'a str> {
+    let mut near = names
+        .iter()
+        .filter_map(|n| {
+            let d = levenshtein(name, n);
+            (d <= (n.chars().count() / 3).max(1)).then_some((d, n.as_str()))
+        })
+        .collect::<Vec<_>>();
+    near.sort();
+    near.dedup();
+    near.into_iter().take(max).map(|(_, n)| n).collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn edits_are_counted() {
+        assert_eq!(levenshtein("staus", "status"), 1);
+        assert_eq!(levenshtein("kitten", "sitting"), 3);
+        assert_eq!(levenshtein("", "abc"), 3);
+        assert_eq!(levenshtein("héllo", "hello"), 1);
+    }
+
+    #[test]
+    fn only_near_names_are_offered() {
+        let names = ["status", "start", "stop", "st", "restart"].map(String::from);
+        assert_eq!(closest("staus", &names, 3), ["status"]);
+        assert_eq!(closest("stat", &names, 1), ["start"]);
+        assert!(closest("xyzzy", &names, 3).is_empty());
+    }
+}
@@ -0,0 +1,204 @@
+use promkit::{
+    buffer::Buffer,
+    crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers},
+    grapheme::{Grapheme, Graphemes},
+    readline::State,
+    termutil, EventHandleFn,
+};
+use std::{cell::RefCell, io, rc::Rc};
+
+// besides letters and digits, so `--dry-run` or `./a/b.txt` move as one word
+pub(crate) const WORD_CHARS: &str = "-_/.";
+
+fn in_word(ch: char, chars: &str) -> bool {
+    ch.is_alphanumeric() || chars.contains(ch)
+}
+
+// where the word before `at` starts, skipping what separates them first
+pub(crate) fn word_start(data: &Graphemes, at: usize, chars: &str) -> usize {
+    let mut i = at.min(data.len());
+    while i > 0 && !in_word(data[i - 1].ch, chars) {
+        i -= 1;
+    }
+    while i > 0 && in_word(data[i - 1].ch, chars) {
+        i -= 1;
+    }
+    i
+}
+
+// where the word after `at` ends
+pub(crate) fn word_end(data: &Graphemes, at: usize, chars: &str) -> usize {
+    let mut i = at.min(data.len());
+    while i < data.len() && !in_word(data[i].ch, chars) {
+        i += 1;
+    }
+    while i < data.len() && in_word(data[i].ch, chars) {
+        i += 1;
+    }
+    i
+}
+
+// where the next word starts, the way vi's `w` goes
+pub(crate) fn word_after(data: &Graphemes, at: usize, chars: &str) -> usize {
+    let mut i = at.min(data.len());
+    while i < data.len() && in_word(data[i].ch, chars) {
+        i += 1;
+    }
+    while i < data.len() && !in_word(data[i].ch, chars) {
+        i += 1;
+    }
+    i
+}
+
+// takes the graphemes between `from` and `to` out, leaving the cursor where they were
+pub(crate) fn cut(editor: &mut Buffer, from: usize, to: usize) -> String {
+    let cut = editor.data.drain(from..to).map(|g| g.ch).collect();
+    editor.position.set(from);
+    cut
+}
+
+pub(crate) fn key(code: KeyCode, modifiers: KeyModifiers) -> Event {
+    Event::Key(KeyEvent { code, modifiers })
+}
+
+pub(crate) type Motion = fn(&Graphemes, usize, &str) -> usize;
+
+pub(crate) fn move_to(chars: Rc<str>, to: Motion) -> Box<EventHandleFn<State>> {
+    Box::new(move |_, _, out: &mut io::Stdout, state: &mut State| {
+        let editor = &state.0.editor;
+        let from = editor.width_to_position();
+        editor
+            .position
+            .set(to(&editor.data, editor.position(), &chars));
+        // the terminal's cursor follows, nothing else is drawn again
+        let to = editor.width_to_position();
+        match to > from {
+            true => termutil::move_right(out, (to - from) as u16),
+            false => termutil::move_left(out, (from - to) as u16),
+        }?;
+        Ok(false)
+    })
+}
+
+// what Ctrl+Y puts back, kills one right after another add up like in readline
+#[derive(Default)]
+pub(crate) struct Killed {
+    text: String,
+    // the line as the last kill left it, if it still looks like that nothing happened since
+    after: Option<(String, usize)>,
+}
+
+pub(crate) type KillRing = Rc<RefCell<Killed>>;
+
+fn kill_to(chars: Rc<str>, killed: KillRing, to: Motion) -> Box<EventHandleFn<State>> {
+    Box::new(move |_, _, _: &mut io::Stdout, state: &mut State| {
+        let editor = &mut state.0.editor;
+        let (at, other) = (
+            editor.position(),
+            to(&editor.data, editor.position(), &chars),
+        );
+        if at == other {
+            return Ok(false);
+        }
+        let now = Some((editor.data.to_string(), at));
+        let text = cut(editor, at.min(other), at.max(other));
+        let mut killed = killed.borrow_mut();
+        killed.text = match killed.after == now {
+            true if other < at => text + &killed.text,
+            true => killed.text.clone() + &text,
+            false => text,
+        };
+        killed.after = Some((editor.data.to_string(), editor.position()));
+        Ok(false)
+    })
+}
+
+fn yank(killed: KillRing) -> Box<EventHandleFn<State>> {
+    Box::new(move |_, _, _: &mut io::Stdout, state: &mut State| {
+        for ch in killed.borrow().text.chars() {
+            state.0.editor.insert(Grapheme::from(ch));
+        }
+        Ok(false)
+    })
+}
+
+// Alt+B/Alt+F (and Ctrl+Left/Ctrl+Right) move by word; Ctrl+W, Alt+D, Ctrl+K and Ctrl+U
+// kill back or forth by word or to either end of the line, and Ctrl+Y yanks it back
+pub(crate) fn edit_keys(chars: &str) -> Vec<(Event, Box<EventHandleFn<State>>)> {
+    let chars: Rc<str> = Rc::from(chars);
+    let killed = KillRing::default();
+    vec![
+        (
+            key(KeyCode::Char('b'), KeyModifiers::ALT),
+            move_to(chars.clone(), word_start),
+        ),
+        (
+            key(KeyCode::Left, KeyModifiers::CONTROL),
+            move_to(chars.clone(), word_start),
+        ),
+        (
+            key(KeyCode::Char('f'), KeyModifiers::ALT),
+            move_to(chars.clone(), word_end),
+        ),
+        (
+            key(KeyCode::Right, KeyModifiers::CONTROL),
+            move_to(chars.clone(), word_end),
+        ),
+        (
+            key(KeyCode::Char('w'), KeyModifiers::CONTROL),
+            kill_to(chars.clone(), killed.clone(), word_start),
+        ),
+        (
+            key(KeyCode::Char('d'), KeyModifiers::ALT),
+            kill_to(chars.clone(), killed.clone(), word_end),
+        ),
+        (
+            key(KeyCode::Char('k'), KeyModifiers::CONTROL),
+            kill_to(chars.clone(), killed.clone(), |data, _, _| data.len()),
+        ),
+        (
+            key(KeyCode::Char('u'), KeyModifiers::CONTROL),
+            kill_to(chars, killed.clone(), |_, _, _| 0),
+        ),
+        (key(KeyCode::Char('y'), KeyModifiers::CONTROL), yank(killed)),
+    ]
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn words_keep_their_dashes_and_dots() {
+        let line = Graphemes::from("deploy --dry-run ./a/b.txt");
+        assert_eq!(word_start(&line, line.len(), WORD_CHARS), 17);
+        assert_eq!(word_start(&line, 16, WORD_CHARS), 7);
+        assert_eq!(word_start(&line, 17, WORD_CHARS), 7);
+        assert_eq!(word_end(&line, 0, WORD_CHARS), 6);
+        assert_eq!(word_end(&line, 6, WORD_CHARS), 16);
+        assert_eq!(word_after(&line, 0, WORD_CHARS), 7);
+        // only what is asked for joins a word
+        assert_eq!(word_start(&line, 16, ""), 13);
+    }
+
+    #[test]
+    fn words_count_graphemes() {
+        let line = Graphemes::from("héllo wörld");
+        assert_eq!(word_start(&line, line.len(), WORD_CHARS), 6);
+        assert_eq!(word_end(&line, 0, WORD_CHARS), 5);
+        let mut editor = Buffer::default();
+        editor.replace(&line);
+        assert_eq!(cut(&mut editor, 6, 11), "wörld");
+        assert_eq!(editor.data.to_string(), "héllo ");
+        assert_eq!(editor.position(), 6);
+    }
+
+    #[test]
+    fn words_stop_at_the_ends() {
+        let line = Graphemes::from("  a  ");
+        assert_eq!(word_start(&line, 0, WORD_CHARS), 0);
+        assert_eq!(word_start(&line, 99, WORD_CHARS), 2);
+        assert_eq!(word_end(&line, 3, WORD_CHARS), 5);
+        assert_eq!(word_after(&line, 5, WORD_CHARS), 5);
+    }
+}
@@ -0,0 +1,174 @@
+use crate::keys::PASTED_NEWLINE;
+use promkit::{
+    crossterm::{
+        queue,
+        style::{Color, Print, SetForegroundColor},
+        terminal::{self, Clear, ClearType},
+    },
+    grapheme::Graphemes,
+    readline::State,
+    state::Render,
+    termutil,
+};
+use std::{
+    cell::Cell,
+    env, fs,
+    io::{self, Write},
+    process::Command,
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+// what comes of a line edited into several
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum EditedLines {
+    // one line, the commands separated by `; `
+    #[default]
+    Join,
+    // kept apart, Enter runs them like a batch
+    Batch,
+}
+
+// $VISUAL, then $EDITOR, then vi, or notepad on Windows; either may come with
+// arguments, e.g. `code -w`
+fn editor() -> Vec<String> {
+    let fallback = match cfg!(windows) {
+        true => "notepad",
+        false => "vi",
+    };
+    ["VISUAL", "EDITOR"]
+        .iter()
+        .filter_map(|v| env::var(v).ok())
+        .filter_map(|e| shlex::split(&e))
+        .find(|words| !words.is_empty())
+        .unwrap_or_else(|| vec![fallback.to_string()])
+}
+
+// `text` as the editor left it, `None` if it exited with an error
+fn edit(text: &str) -> io::Result<Option<String>> {
+    terminal::disable_raw_mode()?;
+    let edited = edit_with(&editor(), text);
+    // back for the prompt whatever the editor did
+    terminal::enable_raw_mode()?;
+    edited
+}
+
+// `text` through a temp file and `words`, the file's path last
+fn edit_with(words: &[String], text: &str) -> io::Result<Option<String>> {
+    static COUNT: AtomicUsize = AtomicUsize::new(0);
+    let path = env::temp_dir().join(format!(
+        "icli-{}-{}.sh",
+        std::process::id(),
+        COUNT.fetch_add(1, Ordering::SeqCst)
+    ));
+    fs::write(&path, format!("{}\n", text))?;
+    let status = Command::new(&words[0])
+        .args(&words[1..])
+        .arg(&path)
+        .status();
+    let edited = match status {
+        Ok(s) if s.success() => fs::read_to_string(&path).map(Some),
+        Ok(_) => Ok(None),
+        Err(e) => Err(e),
+    };
+    let _ = fs::remove_file(&path);
+    edited
+}
+
+// the file back into one line, blank lines dropped
+fn line_of(edited: &str, lines: EditedLines) -> String {
+    // nothing in the file may pass for a line break of the batch
+    let edited = edited.replace(PASTED_NEWLINE, &char::REPLACEMENT_CHARACTER.to_string());
+    let kept = edited
+        .lines()
+        .map(str::trim_end)
+        .filter(|l| !l.trim().is_empty())
+        .collect::<Vec<_>>();
+    match lines {
+        EditedLines::Join => kept.join("; "),
+        EditedLines::Batch => kept.join(&PASTED_NEWLINE.to_string()),
+    }
+}
+
+// the line into the editor and back; it is left as it was when the editor fails
+pub(crate) fn edit_line(
+    out: &mut io::Stdout,
+    state: &mut State,
+    lines: EditedLines,
+    listed: &Cell<bool>,
+) -> promkit::Result<bool> {
+    let text = state
+        .0
+        .editor
+        .data
+        .to_string()
+        .replace(PASTED_NEWLINE, "\n");
+    // the prompt off the screen, drawn again after
+    termutil::move_left(
+        out,
+        (state.1.label.width() + state.0.editor.width_to_position()) as u16,
+    )?;
+    queue!(out, Clear(ClearType::FromCursorDown))?;
+    out.flush()?;
+    let edited = edit(&text);
+    if let Ok(Some(edited)) = &edited {
+        state
+            .0
+            .editor
+            .replace(&Graphemes::from(line_of(edited, lines)));
+    }
+    queue!(
+        out,
+        Print('\r'),
+        Clear(ClearType::FromCursorDown),
+        SetForegroundColor(state.1.label_color),
+        Print(state.1.label.to_string()),
+        SetForegroundColor(Color::Reset),
+    )?;
+    out.flush()?;
+    *state.0.prev = Default::default();
+    state.0.next = state.0.editor.clone();
+    state.render(out)?;
+    *state.0.prev = (*state.0.editor).clone();
+    if let Err(e) = edited {
+        // the editor could not be started; shown below the line like a listing
+        crate::listing::show(out, &[format!("editor: {}", e)], 1, Color::Reset)?;
+        listed.set(true);
+    }
+    Ok(false)
+}
+
+#[cfg(all(test, unix))]
+mod tests {
+    use super::*;
+
+    // an editor that runs `script` on the file, which it gets as `$0`
+    fn sh(script: &str) -> Vec<String> {
+        vec!["sh".to_string(), "-c".to_string(), script.to_string()]
+    }
+
+    #[test]
+    fn file_comes_back_as_the_editor_left_it() {
+        let edited = edit_with(&sh(r#"printf 'build\n\ntest  \n' > "$0""#), "make").unwrap();
+        assert_eq!(edited.as_deref(), Some("build\n\ntest  \n"));
+        // what the editor is handed is the line
+        let kept = edit_with(&sh("true"), "make all").unwrap();
+        assert_eq!(kept.as_deref(), Some("make all\n"));
+    }
+
+    #[test]
+    fn failing_editor_gives_nothing() {
+        let edited = edit_with(&sh(r#"echo changed > "$0"; exit 1"#), "make").unwrap();
+        assert_eq!(edited, None);
+        assert!(edit_with(&["icli-no-such-editor".to_string()], "make").is_err());
+    }
+
+    #[test]
+    fn lines_are_joined_or_batched() {
+        let edited = "build\n\ntest  \n";
+        assert_eq!(line_of(edited, EditedLines::Join), "build; test");
+        assert_eq!(
+            line_of(edited, EditedLines::Batch),
+            format!("build{}test", PASTED_NEWLINE)
+        );
+    }
+}
@@ -0,0 +1,120 @@
+use std::fmt;
+
+#[derive(Debug)]
+pub enum CliError {
+    // the line could not be split into words, e.g. an unterminated quote
+    Lex(String),
+    // usage errors as well as the help and version output clap produces
+    Clap(clap::Error),
+    Io(std::io::Error),
+    // a script file that could not be read
+    File {
+        path: std::path::PathBuf,
+        source: std::io::Error,
+    },
+    // the prompt could not be set up
+    Prompt(String),
+    UnknownCommand {
+        name: String,
+        suggestions: Vec<String>,
+    },
+    AmbiguousCommand {
+        name: String,
+        candidates: Vec<String>,
+    },
+    UndefinedVariable(String),
+    // a `!` history reference that matches no entry
+    EventNotFound(String),
+    // an alias that ends up expanding to itself
+    AliasLoop(String),
+    // a script that cannot be split into commands
+    Script {
+        line: usize,
+        message: String,
+    },
+    // a task returned TaskAction::Error
+    Task(String),
+    // a command of a script failed
+    Batch {
+        line: usize,
+        text: String,
+        source: Box<CliError>,
+    },
+    // a task registered under a name or alias another command has, `help` included
+    DuplicateCommand(String),
+}
+
+fn quoted(names: &[String]) -> String {
+    names
+        .iter()
+        .map(|n| format!("'{}'", n))
+        .collect::<Vec<_>>()
+        .join(", ")
+}
+
+impl fmt::Display for CliError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CliError::Lex(e) => write!(f, "error: {}", e),
+            CliError::Clap(e) => write!(f, "{}", e),
+            CliError::Io(e) => write!(f, "error: {}", e),
+            CliError::File { path, source } => write!(f, "error: {}: {}", path.display(), source),
+            CliError::Prompt(e) => write!(f, "error: {}", e),
+            CliError::UnknownCommand { name, suggestions } => {
+                write!(f, "error: unknown command '{}'", name)?;
+                if !suggestions.is_empty() {
+                    write!(f, ", did you mean {}?", quoted(suggestions))?;
+                }
+                write!(f, "\n\nFor more information, try 'help'.\n")
+            }
+            CliError::AmbiguousCommand { name, candidates } => writeln!(
+                f,
+                "error: ambiguous command '{}', could be {}",
+                name,
+                quoted(candidates)
+            ),
+            CliError::UndefinedVariable(name) => write!(f, "error: undefined variable '{}'", name),
+            CliError::EventNotFound(event) => write!(f, "error: {}: event not found", event),
+            CliError::AliasLoop(name) => write!(f, "error: alias '{}' expands to itself", name),
+            CliError::Script { line, message } => write!(f, "error: line {}: {}", line, message),
+            CliError::Task(message) => write!(f, "error: {}", message),
+            CliError::Batch { line, text, source } => {
+                write!(f, "line {}: {}\n{}", line, text, source)
+            }
+            CliError::DuplicateCommand(name) => {
+                write!(f, "error: there is a command '{}' already", name)
+            }
+        }
+    }
+}
+
+impl std::error::Error for CliError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            CliError::Clap(e) => Some(e),
+            CliError::Io(e) => Some(e),
+            CliError::File { source, .. } => Some(source),
+            CliError::Batch { source, .. } => Some(source.as_ref()),
+            _ => None,
+        }
+    }
+}
+
+impl From<clap::Error> for CliError {
+    fn from(e: clap::Error) -> Self {
+        CliError::Clap(e)
+    }
+}
+
+impl From<std::io::Error> for CliError {
+    fn from(e: std::io::Error) -> Self {
+        CliError::Io(e)
+    }
+}
+
+// for callers still dealing in the rendered messages
+impl From<CliError> for String {
+    fn from(e: CliError) -> Self {
+        e.to_string()
+    }
+}
@@ -0,0 +1,55 @@
+use crate::listing;
+use promkit::{
+    crossterm::{
+        cursor::{RestorePosition, SavePosition},
+        execute,
+        style::{Attribute, Color, Print, PrintStyledContent, SetAttribute, Stylize},
+    },
+    readline::State,
+    termutil,
+};
+use std::{io::Write, thread, time::Duration};
+
+// what Tab does when there is nothing to complete
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum NoMatchFeedback {
+    #[default]
+    Silent,
+    Bell,
+    Flash,
+    Message,
+}
+
+impl NoMatchFeedback {
+    // returns whether something was left below the line to clear on the next key
+    pub(crate) fn give<W: Write>(self, out: &mut W, state: &State) -> std::io::Result<bool> {
+        match self {
+            NoMatchFeedback::Silent => Ok(false),
+            NoMatchFeedback::Bell => execute!(out, Print('\x07')).map(|_| false),
+            NoMatchFeedback::Flash => {
+                let label = state.1.label.to_string();
+                let back = (state.0.editor.width_to_position() + state.1.label.width()) as u16;
+                execute!(out, SavePosition)?;
+                termutil::move_left(out, back)?;
+                execute!(
+                    out,
+                    SetAttribute(Attribute::Reverse),
+                    Print(&label),
+                    SetAttribute(Attribute::NoReverse),
+                )?;
+                thread::sleep(Duration::from_millis(100));
+                termutil::move_left(out, state.1.label.width() as u16)?;
+                execute!(
+                    out,
+                    PrintStyledContent(label.with(state.1.label_color)),
+                    RestorePosition
+                )?;
+                Ok(false)
+            }
+            NoMatchFeedback::Message => {
+                listing::show(out, &["(no matches)".to_string()], 1, Color::Reset)?;
+                Ok(true)
+            }
+        }
+    }
+}
@@ -0,0 +1,46 @@
+use crate::{Task, TaskAction};
+use clap::{ArgMatches, Command};
+use std::sync::Mutex;
+
+// a task of a closure; completion comes from its Command like for any other
+pub(crate) struct FnTask<F> {
+    pub(crate) cmd: Command,
+    pub(crate) f: F,
+}
+
+impl<F: Fn(&ArgMatches) -> TaskAction, C> Task<C> for FnTask<F> {
+    fn command(&self) -> Command {
+        self.cmd.clone()
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        (self.f)(matches)
+    }
+}
+
+// a task keeping state of its own between runs, handed to `f` as `&mut S`; a run that
+// panicked leaves the state as it got it so far
+pub struct StatefulTask<S, F> {
+    cmd: Command,
+    state: Mutex<S>,
+    f: F,
+}
+
+impl<S, F: Fn(&mut S, &ArgMatches) -> TaskAction> StatefulTask<S, F> {
+    pub fn new(cmd: Command, state: S, f: F) -> Self {
+        StatefulTask {
+            cmd,
+            state: Mutex::new(state),
+            f,
+        }
+    }
+}
+
+impl<S, F: Fn(&mut S, &ArgMatches) -> TaskAction, C> Task<C> for StatefulTask<S, F> {
+    fn command(&self) -> Command {
+        self.cmd.clone()
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        (self.f)(&mut state, matches)
+    }
+}
@@ -0,0 +1,72 @@
+const MATCH: i64 = 16;
+const CONSECUTIVE: i64 = 8;
+const WORD_START: i64 = 12;
+const GAP: i64 = 1;
+const LEADING_GAP: i64 = 2;
+
+fn is_separator(c: char) -> bool {
+    matches!(c, '-' | '_' | '.' | '/' | ' ')
+}
+
+// score `candidate` against `pattern` as a subsequence, higher is better;
+// consecutive hits and hits at word starts (after `-`, `_`, ...) are rewarded
+pub(crate) fn score(candidate: &str, pattern: &str) -> Option<i64> {
+    let mut pattern = pattern.chars().filter(|c| !is_separator(*c)).peekable();
+    let mut score = 0;
+    let mut prev: Option<char> = None;
+    let mut last_hit: Option<usize> = None;
+    for (i, c) in candidate.chars().enumerate() {
+        let p = match pattern.peek() {
+            Some(p) => *p,
+            None => break,
+        };
+        if c == p {
+            score += MATCH;
+            if prev.is_none_or(is_separator) {
+                score += WORD_START;
+            }
+            match last_hit {
+                Some(l) if l + 1 == i => score += CONSECUTIVE,
+                Some(l) => score -= GAP * (i - l - 1) as i64,
+                None => score -= LEADING_GAP * i as i64,
+            }
+            last_hit = Some(i);
+            pattern.next();
+        }
+        prev = Some(c);
+    }
+    if pattern.peek().is_some() {
+        return None;
+    }
+    // prefer the tighter candidate among equal matches
+    Some(score - (candidate.chars().count() - last_hit.map_or(0, |l| l + 1)) as i64 * GAP)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn subsequences_match() {
+        assert!(score("config-set", "cfgset").is_some());
+        assert!(score("config_set", "cfgset").is_some());
+        assert!(score("config-set", "setcfg").is_none());
+        assert!(score("status", "statusx").is_none());
+        // a separator typed is not needed in the candidate
+        assert!(score("configset", "config-set").is_some());
+    }
+
+    #[test]
+    fn word_starts_score_higher() {
+        let hyphen = score("config-set", "cs").unwrap();
+        let inner = score("chaos", "cs").unwrap();
+        assert!(hyphen > inner, "{} {}", hyphen, inner);
+        let underscore = score("config_set", "cs").unwrap();
+        assert_eq!(hyphen, underscore);
+    }
+
+    #[test]
+    fn tighter_matches_score_higher() {
+        assert!(score("set", "set").unwrap() > score("settings", "set").unwrap());
+    }
+}
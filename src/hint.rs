@@ -0,0 +1,83 @@
+use crate::path;
+use clap::ValueHint;
+
+fn file_fields(path: &str, field: impl Fn(&str) -> Vec<String>) -> Vec<String> {
+    let mut r = std::fs::read_to_string(path)
+        .map(|s| {
+            s.lines()
+                .map(|l| l.split('#').next().unwrap_or_default())
+                .flat_map(&field)
+                .collect::<Vec<_>>()
+        })
+        .unwrap_or_default();
+    r.sort();
+    r.dedup();
+    r
+}
+
+// the names after the address of an /etc/hosts line
+fn host_names(l: &str) -> Vec<String> {
+    l.split_whitespace()
+        .skip(1)
+        .map(|s| s.to_string())
+        .collect()
+}
+
+// the login of an /etc/passwd line
+fn user_name(l: &str) -> Vec<String> {
+    l.split(':')
+        .next()
+        .filter(|s| !s.trim().is_empty())
+        .map(|s| vec![s.to_string()])
+        .unwrap_or_default()
+}
+
+fn hostnames() -> Vec<String> {
+    file_fields("/etc/hosts", host_names)
+}
+
+fn usernames() -> Vec<String> {
+    file_fields("/etc/passwd", user_name)
+}
+
+// built-in candidates for args annotated with a `ValueHint`
+pub fn hint_completer(hint: ValueHint, token: &str) -> Vec<String> {
+    match hint {
+        ValueHint::FilePath | ValueHint::AnyPath | ValueHint::ExecutablePath => {
+            path::path_candidates(token, false)
+        }
+        ValueHint::DirPath => path::path_candidates(token, true),
+        ValueHint::Hostname => hostnames(),
+        ValueHint::Username => usernames(),
+        _ => vec![],
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn fields_are_sorted_without_comments_or_repeats() {
+        let path = std::env::temp_dir().join(format!("icli-hosts-{}", std::process::id()));
+        std::fs::write(
+            &path,
+            "127.0.0.1 localhost box\n# 10.0.0.1 hidden\n::1 localhost # ip6\n",
+        )
+        .unwrap();
+        let names = file_fields(path.to_str().unwrap(), host_names);
+        std::fs::remove_file(&path).unwrap();
+        assert_eq!(names, ["box", "localhost"]);
+    }
+
+    #[test]
+    fn a_passwd_line_gives_its_login() {
+        assert_eq!(user_name("root:x:0:0:root:/root:/bin/sh"), ["root"]);
+        assert!(user_name(":x:0:0::/:/bin/sh").is_empty());
+    }
+
+    #[test]
+    fn unknown_hints_have_no_candidates() {
+        assert!(hint_completer(ValueHint::Url, "http").is_empty());
+    }
+}
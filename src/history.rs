@@ -0,0 +1,377 @@
+use crate::{keys, lex, CliError};
+use promkit::grapheme::Graphemes;
+use std::{
+    cell::RefCell,
+    fs,
+    io::{self, Write},
+    path::PathBuf,
+    rc::Rc,
+};
+
+// the lines entered at the prompt, oldest first; `!N` counts from 1
+#[derive(Debug, Default)]
+pub(crate) struct History {
+    pub(crate) entries: Vec<String>,
+    // where the entries are kept between sessions, one per line
+    pub(crate) file: Option<PathBuf>,
+    pub(crate) limit: Option<usize>,
+    // an entry equal to the one before it is not added again
+    pub(crate) dedup_consecutive: bool,
+    // an entry that is already there moves to the end
+    pub(crate) dedup_all: bool,
+    // lines starting with a space are not recorded at all
+    pub(crate) ignore_leading_space: bool,
+    // nor are lines of nothing but whitespace
+    pub(crate) skip_blank: bool,
+}
+
+pub(crate) type SharedHistory = Rc<RefCell<History>>;
+
+// a line break in an entry would split it in two
+fn escape(line: &str) -> String {
+    line.replace('\\', "\\\\").replace('\n', "\\n")
+}
+
+fn unescape(line: &str) -> String {
+    let mut r = String::new();
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('n') => r.push('\n'),
+                Some(c) => r.push(c),
+                None => r.push('\\'),
+            },
+            c => r.push(c),
+        }
+    }
+    r
+}
+
+impl History {
+    // a file that cannot be read is an error, the session still starts without it
+    pub(crate) fn load(&mut self) -> Result<(), CliError> {
+        let path = match &self.file {
+            Some(p) => p,
+            None => return Ok(()),
+        };
+        match fs::read_to_string(path) {
+            Ok(s) => {
+                self.entries = s.lines().map(unescape).collect();
+                match self.trim(None) {
+                    true => self.save(),
+                    false => Ok(()),
+                }
+            }
+            Err(e) if e.kind() == io::ErrorKind::NotFound => Ok(()),
+            Err(e) => Err(self.failed(e)),
+        }
+    }
+
+    fn failed(&self, source: io::Error) -> CliError {
+        CliError::File {
+            path: self.file.clone().unwrap_or_default(),
+            source,
+        }
+    }
+
+    // rewrites the file with what is left after trimming
+    pub(crate) fn save(&self) -> Result<(), CliError> {
+        if let Some(path) = &self.file {
+            let lines = self
+                .entries
+                .iter()
+                .map(|e| escape(e) + "\n")
+                .collect::<String>();
+            fs::write(path, lines).map_err(|e| self.failed(e))?;
+        }
+        Ok(())
+    }
+
+    pub(crate) fn clear(&mut self) -> Result<(), CliError> {
+        self.entries.clear();
+        self.save()
+    }
+
+    fn append(&self, line: &str) -> Result<(), CliError> {
+        if let Some(path) = &self.file {
+            fs::OpenOptions::new()
+                .create(true)
+                .append(true)
+                .open(path)
+                .and_then(|mut f| writeln!(f, "{}", escape(line)))
+                .map_err(|e| self.failed(e))?;
+        }
+        Ok(())
+    }
+
+    // drops the oldest entries over the limit, true if there were any
+    fn trim(&mut self, limit: Option<usize>) -> bool {
+        let over = match limit.or(self.limit) {
+            Some(limit) => self.entries.len().saturating_sub(limit),
+            None => 0,
+        };
+        self.entries.drain(..over);
+        over > 0
+    }
+
+    // `limit`, the prompt's, wins over the history's own; the entry is kept even when
+    // the file could not take it
+    pub(crate) fn push(&mut self, line: &str, limit: Option<usize>) -> Result<(), CliError> {
+        if line.is_empty() || (self.ignore_leading_space && line.starts_with(' ')) {
+            return Ok(());
+        }
+        // ` status` is `status` again
+        let line = match line.trim() {
+            "" if !self.skip_blank => line,
+            trimmed => trimmed,
+        };
+        if line.is_empty()
+            || (self.dedup_consecutive && self.entries.last().is_some_and(|l| l == line))
+        {
+            return Ok(());
+        }
+        let before = self.entries.len();
+        if self.dedup_all {
+            self.entries.retain(|l| l != line);
+        }
+        self.entries.push(line.to_string());
+        self.trim(limit);
+        // an entry moved from the middle cannot be appended
+        match self.entries.len() <= before {
+            true => self.save(),
+            false => self.append(line),
+        }
+    }
+
+    // promkit browses its own copy, which always ends in the line being edited; line
+    // breaks are shown the way a paste shows them, and read back the same way
+    pub(crate) fn sync(&self, h: &mut promkit::history::History) {
+        h.data = self
+            .entries
+            .iter()
+            .map(|e| {
+                let e = e.replace(
+                    keys::PASTED_NEWLINE,
+                    &char::REPLACEMENT_CHARACTER.to_string(),
+                );
+                Graphemes::from(e.replace('\n', &keys::PASTED_NEWLINE.to_string()))
+            })
+            .chain([Graphemes::default()])
+            .collect();
+        h.position.set(h.data.len() - 1);
+    }
+
+    fn event(&self, designator: &str) -> Option<&str> {
+        let e = &self.entries;
+        let found = match designator {
+            "!" => e.last(),
+            d if d.bytes().all(|b| b.is_ascii_digit()) => d
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| e.get(n.checked_sub(1)?)),
+            d if d.starts_with('-') && d[1..].bytes().all(|b| b.is_ascii_digit()) => d[1..]
+                .parse::<usize>()
+                .ok()
+                .and_then(|n| e.get(e.len().checked_sub(n)?)),
+            prefix => e.iter().rev().find(|l| l.starts_with(prefix)),
+        };
+        found.map(|l| l.as_str())
+    }
+
+    // `line` with `!!`, `!N`, `!-N` and `!prefix` replaced by the lines they refer to,
+    // `None` if there was nothing to replace; quoted and escaped `!` stay as they are
+    pub(crate) fn expand(&self, line: &str) -> Result<Option<String>, CliError> {
+        let mut out = String::new();
+        let mut start = 0;
+        for (i, _) in lex::unquoted(line).into_iter().filter(|&(_, c)| c == '!') {
+            // the second `!` of `!!`
+            if i < start {
+                continue;
+            }
+            let rest = &line[i + 1..];
+            let len = match rest.chars().next() {
+                Some('!') => 1,
+                // a lone `!` or `!=` is no designator
+                None | Some('=' | '(') => continue,
+                Some(c) if c.is_whitespace() => continue,
+                _ => rest
+                    .find(|c: char| c.is_whitespace() || ";&|'\"".contains(c))
+                    .unwrap_or(rest.len()),
+            };
+            let designator = &rest[..len];
+            let event = self
+                .event(designator)
+                .ok_or_else(|| CliError::EventNotFound(format!("!{}", designator)))?;
+            out.push_str(&line[start..i]);
+            out.push_str(event);
+            start = i + 1 + len;
+        }
+        if start == 0 {
+            return Ok(None);
+        }
+        out.push_str(&line[start..]);
+        Ok(Some(out))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn file_errors_are_handed_back() {
+        // a directory takes no lines
+        let mut h = History {
+            file: Some(std::env::temp_dir()),
+            ..Default::default()
+        };
+        assert!(matches!(h.push("status", None), Err(CliError::File { .. })));
+        assert_eq!(h.entries, ["status"]);
+        assert!(matches!(h.save(), Err(CliError::File { .. })));
+        assert!(matches!(h.load(), Err(CliError::File { .. })));
+    }
+
+    fn history(lines: &[&str]) -> History {
+        let mut h = History::default();
+        for l in lines {
+            h.push(l, None).unwrap();
+        }
+        h
+    }
+
+    #[test]
+    fn designators_refer_to_earlier_lines() {
+        let h = history(&["ls -l", "cd /tmp", "ls"]);
+        let expanded = |l| h.expand(l).unwrap();
+        assert_eq!(expanded("!! x").as_deref(), Some("ls x"));
+        assert_eq!(expanded("!2").as_deref(), Some("cd /tmp"));
+        assert_eq!(expanded("!-3;!!").as_deref(), Some("ls -l;ls"));
+        assert_eq!(expanded("echo !cd").as_deref(), Some("echo cd /tmp"));
+    }
+
+    #[test]
+    fn quoted_and_lone_bangs_stay() {
+        let h = history(&["ls"]);
+        for line in ["echo '!!' \\!!", "a != b", "!", "! x"] {
+            assert_eq!(h.expand(line).unwrap(), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn unknown_events_are_errors() {
+        let h = history(&["ls"]);
+        assert!(matches!(h.expand("!7"), Err(CliError::EventNotFound(e)) if e == "!7"));
+        assert!(h.expand("!cd").is_err());
+    }
+
+    #[test]
+    fn the_limit_drops_the_oldest_lines() {
+        let mut h = history(&["a", "", "b"]);
+        h.push("c", Some(2)).unwrap();
+        assert_eq!(h.entries, ["b", "c"]);
+    }
+
+    fn scratch(name: &str) -> PathBuf {
+        let path = std::env::temp_dir().join(format!("icli-{}-{}", name, std::process::id()));
+        let _ = fs::remove_file(&path);
+        path
+    }
+
+    #[test]
+    fn entries_outlive_the_session() {
+        let path = scratch("history-kept");
+        let mut h = History {
+            file: Some(path.clone()),
+            ..Default::default()
+        };
+        h.push("echo 'a\nb' \\n", None).unwrap();
+        h.push("ls", None).unwrap();
+        let mut next = History {
+            file: Some(path.clone()),
+            ..Default::default()
+        };
+        next.load().unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(next.entries, ["echo 'a\nb' \\n", "ls"]);
+    }
+
+    #[test]
+    fn loading_trims_the_file_to_the_limit() {
+        let path = scratch("history-trimmed");
+        fs::write(&path, "a\nb\nc\n").unwrap();
+        let mut h = History {
+            file: Some(path.clone()),
+            limit: Some(2),
+            ..Default::default()
+        };
+        h.load().unwrap();
+        let kept = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(h.entries, ["b", "c"]);
+        assert_eq!(kept, "b\nc\n");
+    }
+
+    #[test]
+    fn repeats_can_be_skipped() {
+        let mut h = History {
+            dedup_consecutive: true,
+            ..Default::default()
+        };
+        for l in ["a", "a", "b", "a"] {
+            h.push(l, None).unwrap();
+        }
+        assert_eq!(h.entries, ["a", "b", "a"]);
+        let mut h = History {
+            dedup_all: true,
+            ..Default::default()
+        };
+        for l in ["a", "b", "c", "a"] {
+            h.push(l, None).unwrap();
+        }
+        assert_eq!(h.entries, ["b", "c", "a"]);
+    }
+
+    #[test]
+    fn a_moved_entry_rewrites_the_file() {
+        let path = scratch("history-moved");
+        let mut h = History {
+            file: Some(path.clone()),
+            dedup_all: true,
+            ..Default::default()
+        };
+        for l in ["a", "b", "a"] {
+            h.push(l, None).unwrap();
+        }
+        let kept = fs::read_to_string(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(kept, "b\na\n");
+    }
+
+    #[test]
+    fn space_prefixed_lines_can_stay_out() {
+        let mut h = History {
+            ignore_leading_space: true,
+            ..Default::default()
+        };
+        h.push(" secret", None).unwrap();
+        h.push("ls", None).unwrap();
+        assert_eq!(h.entries, ["ls"]);
+    }
+
+    #[test]
+    fn entries_are_stored_trimmed_without_blank_ones() {
+        let mut h = History {
+            skip_blank: true,
+            dedup_consecutive: true,
+            ..Default::default()
+        };
+        for l in ["  ls -l ", "   ", "ls -l\t", "cd"] {
+            h.push(l, None).unwrap();
+        }
+        assert_eq!(h.entries, ["ls -l", "cd"]);
+        let mut h = History::default();
+        h.push("  ", None).unwrap();
+        assert_eq!(h.entries, ["  "]);
+    }
+}
@@ -0,0 +1,85 @@
+use crate::TaskAction;
+use promkit::{
+    build::Builder,
+    crossterm::{event, terminal},
+    readline::{self, State},
+    Output, Prompt,
+};
+use std::{io, time::Duration};
+
+// what happens once nothing was typed for the idle timeout
+pub enum IdleAction {
+    Exit,
+    // run like a line typed, e.g. "logout"
+    RunCommand(String),
+    Callback(Box<dyn Fn() -> TaskAction + Send + Sync>),
+}
+
+// promkit clears the screen on the first prompt it runs, whichever; this lets it get that
+// over with now, so it does not happen later on a task's own prompt
+pub(crate) fn clear_once() {
+    if let Ok(mut prompt) = readline::Builder::default().build() {
+        prompt.initialize = Some(Box::new(|_, _| Err(io::ErrorKind::Other.into())));
+        prompt.finalize = None;
+        let _ = prompt.run();
+    }
+}
+
+// `Prompt::run`, except that it gives up with `TimedOut` after `timeout` without an event
+pub(crate) fn run_until(prompt: &mut Prompt<State>, timeout: Duration) -> promkit::Result<String> {
+    terminal::enable_raw_mode()?;
+    let r = run_raw(prompt, timeout);
+    terminal::disable_raw_mode()?;
+    r
+}
+
+fn run_raw(prompt: &mut Prompt<State>, timeout: Duration) -> promkit::Result<String> {
+    let Prompt {
+        out,
+        handler,
+        pre_run,
+        post_run,
+        initialize,
+        finalize,
+        state,
+    } = prompt;
+    let finish = |out: &mut io::Stdout, state: &mut State, e: io::Error| {
+        if let Some(finalize) = finalize.as_ref() {
+            finalize(out, state)?;
+        }
+        Err(e)
+    };
+    if let Some(initialize) = initialize {
+        if let Err(e) = initialize(out, state) {
+            return finish(out, state, e);
+        }
+    }
+    loop {
+        if let Some(pre_run) = pre_run {
+            if let Err(e) = pre_run(out, state) {
+                return finish(out, state, e);
+            }
+        }
+        if !event::poll(timeout)? {
+            return finish(out, state, io::ErrorKind::TimedOut.into());
+        }
+        let ev = event::read()?;
+        let handled = handler.borrow_mut().handle(ev, out, state);
+        match handled {
+            Ok(true) => {
+                let line = state.output();
+                if let Some(finalize) = finalize.as_ref() {
+                    finalize(out, state)?;
+                }
+                return Ok(line);
+            }
+            Err(e) => return finish(out, state, e),
+            Ok(false) => {}
+        }
+        if let Some(post_run) = post_run {
+            if let Err(e) = post_run(out, state) {
+                return finish(out, state, e);
+            }
+        }
+    }
+}
@@ -0,0 +1,258 @@
+use crate::{edit, keys, theme::PromptTheme, CliError};
+use promkit::{
+    build::Builder,
+    crossterm::event::{Event, KeyCode, KeyModifiers},
+    grapheme::Graphemes,
+    history::History,
+    keybind::KeyBind,
+    readline::{self, State},
+    EventHandleFn, Handler,
+};
+use std::{
+    cell::Cell,
+    io::{self, IsTerminal},
+};
+
+thread_local! {
+    // while a batch or piped input runs, and what `confirm` answers then, if not its default
+    static UNATTENDED: Cell<Option<Option<bool>>> = const { Cell::new(None) };
+    // the theme of the cli whose task is running, for the sub prompts it opens
+    static THEME: Cell<Option<PromptTheme>> = const { Cell::new(None) };
+}
+
+// no one there to answer until it is dropped
+pub(crate) struct Unattended(Option<Option<bool>>);
+
+impl Unattended {
+    pub(crate) fn start(answer: Option<bool>) -> Unattended {
+        Unattended(UNATTENDED.with(|u| u.replace(Some(answer))))
+    }
+}
+
+impl Drop for Unattended {
+    fn drop(&mut self) {
+        UNATTENDED.with(|u| u.set(self.0));
+    }
+}
+
+// the sub prompts take `theme` until it is dropped
+pub(crate) struct Themed(Option<PromptTheme>);
+
+impl Themed {
+    pub(crate) fn start(theme: PromptTheme) -> Themed {
+        Themed(THEME.with(|t| t.replace(Some(theme))))
+    }
+}
+
+impl Drop for Themed {
+    fn drop(&mut self) {
+        THEME.with(|t| t.set(self.0));
+    }
+}
+
+// the keys as `keys::normalize` has them, for the plain key bindings of promkit
+struct Normalized(KeyBind<State>);
+
+impl Handler<State> for Normalized {
+    fn handle(
+        &mut self,
+        ev: Event,
+        out: &mut io::Stdout,
+        state: &mut State,
+    ) -> promkit::Result<bool> {
+        self.0.handle(keys::normalize(ev), out, state)
+    }
+}
+
+// a prompt of a task's own, e.g. for the questions of a wizard; the session's prompt
+// is not running meanwhile, and nothing typed here gets into its history. It is drawn
+// in the theme of the cli running the task unless given one
+pub struct SubPrompt {
+    label: String,
+    mask: Option<char>,
+    candidates: Vec<String>,
+    default: Option<String>,
+    theme: PromptTheme,
+    // kept between reads of the same sub prompt
+    history: Option<Box<History>>,
+}
+
+pub fn sub_prompt(label: impl Into<String>) -> SubPrompt {
+    SubPrompt {
+        label: label.into(),
+        mask: None,
+        candidates: Vec::new(),
+        default: None,
+        theme: THEME.with(|t| t.get()).unwrap_or_default(),
+        history: None,
+    }
+}
+
+impl SubPrompt {
+    // what Tab completes from
+    pub fn suggest<L: IntoIterator<Item = S>, S: Into<String>>(mut self, candidates: L) -> Self {
+        self.candidates = candidates.into_iter().map(Into::into).collect();
+        self
+    }
+
+    // in the line to begin with; piped in, it is what an empty line stands for
+    pub fn default(mut self, text: impl Into<String>) -> Self {
+        self.default = Some(text.into());
+        self
+    }
+
+    // shown for every character typed, and nothing is kept
+    pub fn mask(mut self, mask: char) -> Self {
+        self.mask = Some(mask);
+        self
+    }
+
+    pub fn theme(mut self, theme: PromptTheme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    fn keys(&self) -> Normalized {
+        let mut b = KeyBind::default();
+        b.assign(edit::edit_keys(edit::WORD_CHARS));
+        let candidates = self.candidates.clone();
+        b.assign(vec![(
+            edit::key(KeyCode::Tab, KeyModifiers::NONE),
+            Box::new(move |_, _, _: &mut io::Stdout, state: &mut State| {
+                let line = state.0.editor.data.to_string();
+                state
+                    .0
+                    .editor
+                    .replace(&crate::complete(candidates.clone(), &line));
+                Ok(false)
+            }) as Box<EventHandleFn<State>>,
+        )]);
+        Normalized(b)
+    }
+
+    // on a prompt of its own, or the next line of whatever is piped in
+    pub fn read(&mut self) -> Result<String, CliError> {
+        if !io::stdin().is_terminal() {
+            let mut line = String::new();
+            if io::stdin().read_line(&mut line)? == 0 {
+                return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+            }
+            let len = line.trim_end_matches(['\r', '\n']).len();
+            line.truncate(len);
+            return Ok(match &self.default {
+                Some(default) if line.is_empty() => default.clone(),
+                _ => line,
+            });
+        }
+        let mut b = readline::Builder::default()
+            .label(&self.label)
+            .label_color(self.theme.label)
+            .handler(self.keys());
+        if let Some(mask) = self.mask {
+            b = b.mask(mask);
+        }
+        let mut prompt = b.build().map_err(|e| CliError::Prompt(e.to_string()))?;
+        prompt.state.1.hstr = match self.mask {
+            // not even for the up arrow
+            Some(_) => None,
+            None => Some(self.history.take().unwrap_or_default()),
+        };
+        if let Some(default) = &self.default {
+            let editor = &mut prompt.state.0.editor;
+            editor.replace(&Graphemes::from(default.as_str()));
+            // drawn by the first render like typed
+            prompt.state.0.next = editor.clone();
+        }
+        // raw mode ends with `run`, Ctrl+C included
+        let r = prompt.run();
+        self.history = prompt.state.1.hstr.take();
+        Ok(r?)
+    }
+}
+
+// for a task asking for more than its arguments
+pub fn read_line(label: &str) -> Result<String, CliError> {
+    sub_prompt(label).read()
+}
+
+// echoed as `*`, for passwords and the like
+pub fn read_secret(label: &str) -> Result<String, CliError> {
+    sub_prompt(label).mask('*').read()
+}
+
+// `question [y/N]` until it gets a yes or a no, Enter taking `default` and Ctrl+C a no;
+// never asked with nobody to answer, in a batch or with stdin piped
+pub fn confirm(question: &str, default: bool) -> Result<bool, CliError> {
+    let unattended = UNATTENDED.with(|u| u.get());
+    if unattended.is_some() || !io::stdin().is_terminal() {
+        return Ok(unattended.flatten().unwrap_or(default));
+    }
+    let label = match default {
+        true => format!("{} [Y/n] ", question),
+        false => format!("{} [y/N] ", question),
+    };
+    loop {
+        let answer = match read_line(&label) {
+            Ok(answer) => answer,
+            Err(CliError::Io(e)) if e.kind() == io::ErrorKind::Interrupted => return Ok(false),
+            Err(e) => return Err(e),
+        };
+        if let Some(yes) = answered(&answer, default) {
+            return Ok(yes);
+        }
+    }
+}
+
+// what a reply to `confirm` says, `None` if it is neither a yes nor a no
+fn answered(reply: &str, default: bool) -> Option<bool> {
+    match reply.trim() {
+        "" => Some(default),
+        "y" | "Y" | "yes" => Some(true),
+        "n" | "N" | "no" => Some(false),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use promkit::crossterm::style::Color;
+
+    #[test]
+    fn sub_prompts_take_the_running_theme() {
+        assert_eq!(sub_prompt("name: ").theme, PromptTheme::default());
+        let theme = PromptTheme {
+            label: Color::Cyan,
+            ..Default::default()
+        };
+        {
+            let _themed = Themed::start(theme);
+            assert_eq!(sub_prompt("name: ").theme, theme);
+            let own = PromptTheme::monochrome();
+            assert_eq!(sub_prompt("name: ").theme(own).theme, own);
+        }
+        assert_eq!(sub_prompt("name: ").theme, PromptTheme::default());
+    }
+
+    #[test]
+    fn replies_are_a_yes_a_no_or_asked_again() {
+        assert_eq!(answered(" yes ", false), Some(true));
+        assert_eq!(answered("N", true), Some(false));
+        assert_eq!(answered("", true), Some(true));
+        assert_eq!(answered("maybe", true), None);
+    }
+
+    #[test]
+    fn nobody_there_gets_the_preset_answer() {
+        {
+            let _batch = Unattended::start(None);
+            assert!(confirm("go?", true).unwrap());
+            {
+                let _nested = Unattended::start(Some(false));
+                assert!(!confirm("go?", true).unwrap());
+            }
+            assert!(!confirm("go?", false).unwrap());
+        }
+        assert_eq!(UNATTENDED.with(|u| u.get()), None);
+    }
+}
@@ -0,0 +1,102 @@
+use crate::{Completions, Task, TaskAction};
+use clap::{ArgMatches, Command};
+use promkit::grapheme::Graphemes;
+use std::{cell::RefCell, rc::Rc, sync::Arc, thread};
+
+// a task that may also run on a job's thread; the context of the cli does not go
+// along, so it never gets one
+pub(crate) struct Shared(pub(crate) Arc<dyn Task + Send + Sync>);
+
+impl<C> Task<C> for Shared {
+    fn command(&self) -> Command {
+        self.0.command()
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        self.0.action(&(), matches)
+    }
+    fn action_raw(&self, _: &C, matches: &ArgMatches, raw: Option<&str>) -> TaskAction {
+        self.0.action_raw(&(), matches, raw)
+    }
+    fn suggests(&self, args: &[&'_ str]) -> Option<Graphemes> {
+        self.0.suggests(args)
+    }
+    fn candidates(&self, args: &[&'_ str]) -> Vec<Graphemes> {
+        self.0.candidates(args)
+    }
+    fn completions(&self, args: &[&'_ str]) -> Option<Completions> {
+        self.0.completions(args)
+    }
+}
+
+struct Job {
+    id: usize,
+    line: String,
+    handle: thread::JoinHandle<TaskAction>,
+}
+
+// what a job ended with, `None` if it panicked
+pub(crate) type Ended = (usize, String, Option<TaskAction>);
+
+// the jobs started with `&`, until they are waited for or reported as done
+#[derive(Default)]
+pub(crate) struct Jobs {
+    last: usize,
+    jobs: Vec<Job>,
+}
+
+pub(crate) type SharedJobs = Rc<RefCell<Jobs>>;
+
+impl Jobs {
+    pub(crate) fn spawn<F: FnOnce() -> TaskAction + Send + 'static>(
+        &mut self,
+        line: &str,
+        f: F,
+    ) -> usize {
+        self.last += 1;
+        self.jobs.push(Job {
+            id: self.last,
+            line: line.to_string(),
+            handle: thread::spawn(f),
+        });
+        self.last
+    }
+
+    fn join(job: Job) -> Ended {
+        (job.id, job.line, job.handle.join().ok())
+    }
+
+    // the jobs that are done by now, taken out of the table
+    pub(crate) fn finished(&mut self) -> Vec<Ended> {
+        let (done, running) = self
+            .jobs
+            .drain(..)
+            .partition::<Vec<_>, _>(|j| j.handle.is_finished());
+        self.jobs = running;
+        done.into_iter().map(Self::join).collect()
+    }
+
+    // blocks until the job is done, `None` for no such job
+    pub(crate) fn wait(&mut self, id: usize) -> Option<Ended> {
+        let i = self.jobs.iter().position(|j| j.id == id)?;
+        Some(Self::join(self.jobs.remove(i)))
+    }
+
+    pub(crate) fn ids(&self) -> Vec<usize> {
+        self.jobs.iter().map(|j| j.id).collect()
+    }
+
+    pub(crate) fn list(&self) -> Vec<(usize, &str, bool)> {
+        self.jobs
+            .iter()
+            .map(|j| (j.id, j.line.as_str(), j.handle.is_finished()))
+            .collect()
+    }
+}
+
+pub(crate) fn report((id, line, action): &Ended) -> String {
+    match action {
+        Some(TaskAction::Error(e)) => format!("[{}] failed   {}: {}", id, line, e),
+        Some(_) => format!("[{}] done     {}", id, line),
+        None => format!("[{}] panicked {}", id, line),
+    }
+}
@@ -0,0 +1,814 @@
+use crate::{
+    cycle::Cycle,
+    listing,
+    printer::{Drawn, PromptPrinter},
+    rprompt::{self, RightLabel},
+    search::{Search, SearchAccept},
+    theme::{self, PromptTheme},
+    vi::{Step, Vi},
+};
+use promkit::{
+    crossterm::{
+        event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+        queue,
+        style::{Attribute, Print, SetAttribute},
+        terminal::{self, Clear, ClearType},
+    },
+    grapheme::{Grapheme, Graphemes},
+    keybind::KeyBind,
+    readline::State,
+    state::Render,
+    termutil, EventHandleFn, Handler,
+};
+use std::{
+    cell::{Cell, RefCell},
+    io::{self, Write},
+    rc::Rc,
+    time::{Duration, Instant},
+};
+
+// the most recent history entry extending what has been typed
+fn history_hint(state: &State) -> Option<Graphemes> {
+    let typed = &state.0.editor.data;
+    if typed.is_empty() || state.0.editor.position() != typed.len() {
+        return None;
+    }
+    state.1.hstr.as_ref()?.data.iter().rev().find_map(|h| {
+        (h.len() > typed.len() && h.starts_with(typed))
+            .then(|| h[typed.len()..].iter().cloned().collect())
+    })
+}
+
+// Ctrl+D deletes under the cursor, and leaves the prompt on an empty line
+pub(crate) fn delete_or_eof() -> Box<EventHandleFn<State>> {
+    Box::new(|_, _, _: &mut io::Stdout, state: &mut State| {
+        let editor = &mut state.0.editor;
+        if editor.data.is_empty() {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+        }
+        if editor.next() {
+            editor.erase();
+        }
+        Ok(false)
+    })
+}
+
+// the leading whitespace plus the following word of a hint
+fn next_word(hint: &Graphemes) -> usize {
+    let spaces = hint.iter().take_while(|g| g.ch.is_whitespace()).count();
+    spaces
+        + hint[spaces..]
+            .iter()
+            .take_while(|g| !g.ch.is_whitespace())
+            .count()
+}
+
+pub(crate) type Highlight = Box<dyn Fn(&str) -> Option<bool>>;
+pub(crate) type Usage = Box<dyn Fn(&str) -> Option<String>>;
+
+// the prompt's key handler, tidying up what the crate drew around the line before each key
+pub(crate) struct Keys {
+    pub(crate) bind: KeyBind<State>,
+    pub(crate) listed: Rc<Cell<bool>>,
+    pub(crate) cycle: Rc<RefCell<Cycle>>,
+    pub(crate) hints: bool,
+    pub(crate) theme: PromptTheme,
+    pub(crate) hint: Option<Graphemes>,
+    pub(crate) search: Option<Search>,
+    // whether the line starts with a known command, `None` for nothing to color
+    pub(crate) highlight: Option<Highlight>,
+    // what a task still wants after the line, only looked at and never taken in
+    pub(crate) usage: Option<Usage>,
+    pub(crate) usage_shown: bool,
+    pub(crate) accept: SearchAccept,
+    // keys bound by the user, never taken over here
+    pub(crate) user: Vec<Event>,
+    pub(crate) vi: Option<Vi>,
+    // an Enter arriving with more input right behind it was pasted, not typed; promkit's
+    // crossterm predates bracketed paste, so this is the only way to tell
+    pub(crate) hold_pastes: bool,
+    pub(crate) ctrl_c: CtrlCBehavior,
+    // a second Ctrl+C this soon after the first leaves, with `ClearLine`
+    pub(crate) double_ctrl_c: Option<Duration>,
+    pub(crate) last_ctrl_c: Option<Instant>,
+    pub(crate) burst: bool,
+    pub(crate) rprompt: Option<RightLabel>,
+    pub(crate) printer: PromptPrinter,
+    // bindings of more than one key
+    pub(crate) chords: Vec<(Vec<Event>, Rc<EventHandleFn<State>>)>,
+    pub(crate) chord_timeout: Duration,
+}
+
+// the keys of a binding, pressed one after the other
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Chord(pub Vec<KeyEvent>);
+
+impl From<KeyEvent> for Chord {
+    fn from(key: KeyEvent) -> Self {
+        Chord(vec![key])
+    }
+}
+
+impl From<Vec<KeyEvent>> for Chord {
+    fn from(keys: Vec<KeyEvent>) -> Self {
+        Chord(keys)
+    }
+}
+
+impl<const N: usize> From<[KeyEvent; N]> for Chord {
+    fn from(keys: [KeyEvent; N]) -> Self {
+        Chord(keys.to_vec())
+    }
+}
+
+// the binding `keys` make, and whether a longer chord goes on from them
+fn chord_lookup(
+    chords: &[(Vec<Event>, Rc<EventHandleFn<State>>)],
+    keys: &[Event],
+) -> (Option<Rc<EventHandleFn<State>>>, bool) {
+    let bound = chords
+        .iter()
+        .find(|(k, _)| *k == keys)
+        .map(|(_, f)| f.clone());
+    let longer = chords
+        .iter()
+        .any(|(k, _)| k.len() > keys.len() && k.starts_with(keys));
+    (bound, longer)
+}
+
+// `C-x` the way emacs names keys
+fn key_name(ev: &Event) -> String {
+    let (code, modifiers) = match ev {
+        Event::Key(KeyEvent { code, modifiers }) => (code, modifiers),
+        _ => return String::new(),
+    };
+    let mut name = String::new();
+    if modifiers.contains(KeyModifiers::CONTROL) {
+        name.push_str("C-");
+    }
+    if modifiers.contains(KeyModifiers::ALT) {
+        name.push_str("M-");
+    }
+    match code {
+        KeyCode::Char(c) => name.push(*c),
+        c => name.push_str(&format!("{:?}", c)),
+    }
+    name
+}
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum CtrlCBehavior {
+    // `^C` after the line, which is dropped for a fresh prompt
+    ClearLine,
+    // only ends a search or a completion cycle, the line stays
+    Interrupt,
+    // leaves the prompt like Ctrl+D
+    Exit,
+    // enters an empty line, as it used to
+    SubmitEmpty,
+}
+
+// stands in for the line breaks of a paste, which only run with one more Enter; a
+// private-use char, and `normalize` makes sure one typed or pasted is never taken for it
+pub(crate) const PASTED_NEWLINE: char = '\u{e000}';
+
+// what is drawn for one, U+E000 itself shows as a box or not at all in most fonts; as
+// wide as it, so the cursor stays where promkit has it
+const PASTED_MARK: char = '↵';
+
+// `data` as it is drawn
+fn drawn(data: &[Grapheme]) -> String {
+    data.iter()
+        .map(|g| match g.ch {
+            PASTED_NEWLINE => PASTED_MARK,
+            ch => ch,
+        })
+        .collect()
+}
+
+fn enter() -> Event {
+    Event::Key(KeyEvent {
+        code: KeyCode::Enter,
+        modifiers: KeyModifiers::NONE,
+    })
+}
+
+// keys the way the bindings name them, whatever the terminal sent: conhost and unix
+// terminals alike report Shift along with BackTab, some Windows consoles give Shift+Tab
+// as Tab, Ctrl+letter as the control character itself, and AltGr as Ctrl+Alt; the
+// stand-in for a pasted line break is kept out of what is typed. Only presses get here:
+// the crossterm promkit brings has no key event kinds and drops the releases Windows
+// reports, so there is nothing to filter yet
+pub(crate) fn normalize(ev: Event) -> Event {
+    let (code, modifiers) = match ev {
+        Event::Key(KeyEvent { code, modifiers }) => (code, modifiers),
+        ev => return ev,
+    };
+    let (code, modifiers) = match code {
+        KeyCode::BackTab => (KeyCode::BackTab, KeyModifiers::NONE),
+        KeyCode::Tab if modifiers.contains(KeyModifiers::SHIFT) => {
+            (KeyCode::BackTab, KeyModifiers::NONE)
+        }
+        KeyCode::Char(PASTED_NEWLINE) => (KeyCode::Char(char::REPLACEMENT_CHARACTER), modifiers),
+        KeyCode::Char(c @ '\u{1}'..='\u{1a}') => (
+            KeyCode::Char((c as u8 + b'a' - 1) as char),
+            modifiers | KeyModifiers::CONTROL,
+        ),
+        KeyCode::Char(c)
+            if modifiers.contains(KeyModifiers::CONTROL | KeyModifiers::ALT)
+                && !c.is_ascii_alphanumeric() =>
+        {
+            (
+                KeyCode::Char(c),
+                modifiers - KeyModifiers::CONTROL - KeyModifiers::ALT,
+            )
+        }
+        code => (code, modifiers),
+    };
+    Event::Key(KeyEvent { code, modifiers })
+}
+
+fn ctrl(ev: &Event, c: char) -> bool {
+    matches!(ev, Event::Key(KeyEvent {
+        code: KeyCode::Char(k),
+        modifiers: KeyModifiers::CONTROL,
+    }) if *k == c)
+}
+
+impl Keys {
+    // a key while searching; `None` once it is up to the usual bindings
+    fn search_key(
+        &mut self,
+        ev: &Event,
+        out: &mut io::Stdout,
+        state: &mut State,
+    ) -> promkit::Result<Option<bool>> {
+        let search = match self.search.as_mut() {
+            Some(s) => s,
+            None if ctrl(ev, 'r') && !self.user.contains(ev) => {
+                self.search = Some(Search::start(out, state)?);
+                return Ok(Some(false));
+            }
+            None => return Ok(None),
+        };
+        match ev {
+            _ if ctrl(ev, 'r') => search.older(out, state)?,
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+            }) => search.push(out, state, *c)?,
+            Event::Key(KeyEvent {
+                code: KeyCode::Backspace,
+                ..
+            }) => search.pop(out, state)?,
+            Event::Key(KeyEvent {
+                code: KeyCode::Enter,
+                ..
+            }) => {
+                if let Some(s) = self.search.take() {
+                    s.finish(out, state, true)?;
+                }
+                return Ok(Some(self.accept == SearchAccept::Run));
+            }
+            Event::Key(KeyEvent {
+                code: KeyCode::Esc, ..
+            }) => {
+                if let Some(s) = self.search.take() {
+                    s.finish(out, state, false)?;
+                }
+            }
+            _ if ctrl(ev, 'g') => {
+                if let Some(s) = self.search.take() {
+                    s.finish(out, state, false)?;
+                }
+            }
+            // any other key takes the match and then does what it always does
+            _ => {
+                if let Some(s) = self.search.take() {
+                    s.finish(out, state, !ctrl(ev, 'c'))?;
+                }
+                return Ok(None);
+            }
+        }
+        Ok(Some(false))
+    }
+
+    fn ctrl_c(&mut self, out: &mut io::Stdout, state: &mut State) -> promkit::Result<bool> {
+        let search = self.search.take();
+        match self.ctrl_c {
+            CtrlCBehavior::ClearLine => {
+                if let Some(s) = search {
+                    s.finish(out, state, false)?;
+                }
+                let now = Instant::now();
+                let again = self.last_ctrl_c.replace(now);
+                if matches!((again, self.double_ctrl_c), (Some(t), Some(d)) if now - t <= d) {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof));
+                }
+                termutil::move_right(out, state.0.editor.width_from_position() as u16)?;
+                state.0.editor.to_tail();
+                queue!(out, Print("^C"))?;
+                if self.double_ctrl_c.is_some() {
+                    queue!(out, Print("\r\npress Ctrl+C again to exit"))?;
+                }
+                out.flush()?;
+                Err(io::Error::from(io::ErrorKind::Interrupted))
+            }
+            CtrlCBehavior::Interrupt => {
+                match search {
+                    Some(s) => s.finish(out, state, false)?,
+                    None => {
+                        let esc = Event::Key(KeyEvent {
+                            code: KeyCode::Esc,
+                            modifiers: KeyModifiers::NONE,
+                        });
+                        self.bind.handle(esc, out, state)?;
+                    }
+                }
+                Ok(false)
+            }
+            CtrlCBehavior::Exit => Err(io::Error::from(io::ErrorKind::UnexpectedEof)),
+            CtrlCBehavior::SubmitEmpty => {
+                if let Some(s) = search {
+                    s.finish(out, state, false)?;
+                }
+                *state.0.editor = Default::default();
+                Ok(true)
+            }
+        }
+    }
+
+    // the line is done with, entered or given up on
+    fn left(&mut self, state: &mut State) {
+        if let Some(vi) = self.vi.as_mut() {
+            vi.reset(state);
+        }
+    }
+
+    // a key in vi mode; `None` once it is up to the usual bindings
+    fn vi_key(
+        &mut self,
+        ev: &Event,
+        out: &mut io::Stdout,
+        state: &mut State,
+    ) -> promkit::Result<Option<bool>> {
+        let vi = match self.vi.as_mut() {
+            Some(vi) if !self.user.contains(ev) => vi,
+            _ => return Ok(None),
+        };
+        if !vi.is_normal() {
+            let esc = Event::Key(KeyEvent {
+                code: KeyCode::Esc,
+                modifiers: KeyModifiers::NONE,
+            });
+            if *ev != esc {
+                return Ok(None);
+            }
+            // whatever Esc does otherwise still happens on the way
+            self.bind.handle(esc, out, state)?;
+            vi.enter_normal(out, state)?;
+            return Ok(Some(false));
+        }
+        match vi.key(ev, out, state)? {
+            Step::Done => Ok(Some(false)),
+            Step::Search => {
+                self.search = Some(Search::start(out, state)?);
+                Ok(Some(false))
+            }
+            Step::Pass => Ok(None),
+        }
+    }
+
+    fn accept_hint(&mut self, ev: &Event) -> Option<usize> {
+        let hint = self.hint.as_ref().filter(|_| !self.user.contains(ev))?;
+        match ev {
+            Event::Key(KeyEvent {
+                code: KeyCode::Right | KeyCode::End,
+                modifiers: KeyModifiers::NONE,
+            })
+            | Event::Key(KeyEvent {
+                code: KeyCode::Char('e'),
+                modifiers: KeyModifiers::CONTROL,
+            }) => Some(hint.len()),
+            Event::Key(KeyEvent {
+                code: KeyCode::Right,
+                modifiers: KeyModifiers::ALT,
+            })
+            | Event::Key(KeyEvent {
+                code: KeyCode::Char('f'),
+                modifiers: KeyModifiers::ALT,
+            }) => Some(next_word(hint)),
+            _ => None,
+        }
+    }
+
+    // the line drawn again over what promkit drew when a paste left line breaks in it
+    fn mark_pastes(&self, out: &mut io::Stdout, state: &mut State) -> promkit::Result<()> {
+        let data = state.0.editor.data.clone();
+        if state.1.mask.is_some() || !data.iter().any(|g| g.ch == PASTED_NEWLINE) {
+            return Ok(());
+        }
+        state.0.next = state.0.editor.clone();
+        state.render(out)?;
+        state.0.prev = state.0.editor.clone();
+        let at = state.0.editor.width_to_position();
+        termutil::move_left(out, at as u16)?;
+        queue!(out, Print(drawn(&data)))?;
+        out.flush()?;
+        termutil::move_left(out, (data.width() - at) as u16)
+    }
+
+    // colors the first word over what promkit drew, the buffer stays as it is
+    fn highlight(&self, out: &mut io::Stdout, state: &mut State) -> promkit::Result<()> {
+        let known = match &self.highlight {
+            Some(f) if state.1.mask.is_none() => f(&state.0.editor.data.to_string()),
+            _ => None,
+        };
+        let known = match known {
+            Some(k) => k,
+            None => return Ok(()),
+        };
+        let data = state.0.editor.data.clone();
+        let spaces = data.iter().take_while(|g| g.ch.is_whitespace()).count();
+        let word = spaces + next_word(&data[spaces..].iter().cloned().collect());
+        state.0.next = state.0.editor.clone();
+        state.render(out)?;
+        state.0.prev = state.0.editor.clone();
+        let at = state.0.editor.width_to_position();
+        let end = data[..word].iter().map(|g| g.width).sum::<usize>();
+        termutil::move_left(out, at as u16)?;
+        queue!(
+            out,
+            Print(drawn(&data[..spaces])),
+            Print(theme::paint(
+                drawn(&data[spaces..word]),
+                match known {
+                    true => self.theme.command,
+                    false => self.theme.unknown,
+                }
+            ))
+        )?;
+        out.flush()?;
+        match at > end {
+            true => termutil::move_right(out, (at - end) as u16),
+            false => termutil::move_left(out, (end - at) as u16),
+        }
+    }
+
+    fn usage_hint(&self, state: &State) -> Option<Graphemes> {
+        let editor = &state.0.editor;
+        if state.1.mask.is_some() || editor.position() != editor.data.len() {
+            return None;
+        }
+        self.usage.as_ref()?(&editor.data.to_string()).map(Graphemes::from)
+    }
+
+    // how wide the hint drawn is
+    fn show_hint(&mut self, out: &mut io::Stdout, state: &mut State) -> promkit::Result<usize> {
+        let line = state.0.editor.data.to_string();
+        if self.listed.get() || self.cycle.borrow().is_active(&line) {
+            return Ok(0);
+        }
+        let history = self.hints.then(|| history_hint(state)).flatten();
+        let hint = match history.clone().or_else(|| self.usage_hint(state)) {
+            Some(h) => h,
+            None => return Ok(0),
+        };
+        // bring the line up to date first, the hint sits right after it
+        state.0.next = state.0.editor.clone();
+        state.render(out)?;
+        state.0.prev = state.0.editor.clone();
+        // never wrap, the hint must stay on the cursor's row
+        let room = (terminal::size()?.0 as usize)
+            .saturating_sub(promkit::crossterm::cursor::position()?.0 as usize + 1);
+        let mut shown = Graphemes::default();
+        for g in hint.iter() {
+            if shown.width() + g.width > room {
+                break;
+            }
+            shown.push(g.clone());
+        }
+        if shown.is_empty() {
+            return Ok(0);
+        }
+        match self.theme.hint {
+            Some(c) => queue!(out, Print(theme::paint(&shown, c)))?,
+            None => queue!(
+                out,
+                SetAttribute(Attribute::Dim),
+                Print(shown.to_string()),
+                SetAttribute(Attribute::NormalIntensity)
+            )?,
+        }
+        out.flush()?;
+        termutil::move_left(out, shown.width() as u16)?;
+        self.usage_shown = history.is_none();
+        self.hint = history;
+        Ok(shown.width())
+    }
+
+    fn show_rprompt(
+        &self,
+        out: &mut io::Stdout,
+        state: &mut State,
+        after: usize,
+    ) -> promkit::Result<()> {
+        let f = match &self.rprompt {
+            Some(f) => f,
+            None => return Ok(()),
+        };
+        state.0.next = state.0.editor.clone();
+        state.render(out)?;
+        state.0.prev = state.0.editor.clone();
+        rprompt::draw(out, state, &f(), after)
+    }
+}
+
+impl Handler<State> for Keys {
+    fn handle(
+        &mut self,
+        ev: Event,
+        out: &mut io::Stdout,
+        state: &mut State,
+    ) -> promkit::Result<bool> {
+        let printer = self.printer.clone();
+        let mut shown = printer.lock();
+        let r = self.key(normalize(ev), out, state);
+        *shown = match r {
+            Ok(false) if self.search.is_none() => {
+                // drawn now rather than on the next round, the way a message would show it
+                state.0.next = state.0.editor.clone();
+                state.render(out)?;
+                state.0.prev = state.0.editor.clone();
+                Some(Drawn::of(state))
+            }
+            _ => None,
+        };
+        r
+    }
+}
+
+impl Keys {
+    // ` C-x-` after the line while the rest of a chord is waited for, nothing for no keys
+    fn show_pending(
+        &self,
+        out: &mut io::Stdout,
+        state: &State,
+        keys: &[Event],
+    ) -> promkit::Result<()> {
+        let rest = state.0.editor.width_from_position();
+        termutil::move_right(out, rest as u16)?;
+        let shown = match keys.is_empty() {
+            true => String::new(),
+            false => format!(
+                " {}-",
+                keys.iter().map(key_name).collect::<Vec<_>>().join(" ")
+            ),
+        };
+        queue!(
+            out,
+            Clear(ClearType::UntilNewLine),
+            SetAttribute(Attribute::Dim),
+            Print(&shown),
+            SetAttribute(Attribute::NormalIntensity)
+        )?;
+        out.flush()?;
+        termutil::move_left(out, (rest + Graphemes::from(shown).width()) as u16)
+    }
+
+    fn starts_chord(&self, ev: &Event) -> bool {
+        self.chords.iter().any(|(keys, _)| keys[0] == *ev)
+    }
+
+    // the rest of a chord `first` starts: what its binding came back with, or the keys read
+    // after `first` if it was not one after all; Esc gives up on it
+    fn chord(
+        &mut self,
+        first: &Event,
+        out: &mut io::Stdout,
+        state: &mut State,
+    ) -> promkit::Result<(Option<bool>, Vec<Event>)> {
+        let mut keys = vec![*first];
+        loop {
+            let (bound, longer) = chord_lookup(&self.chords, &keys);
+            let next = match longer {
+                true => {
+                    self.show_pending(out, state, &keys)?;
+                    let next = match event::poll(self.chord_timeout)? {
+                        true => Some(normalize(event::read()?)),
+                        false => None,
+                    };
+                    self.show_pending(out, state, &[])?;
+                    next
+                }
+                false => None,
+            };
+            match next {
+                Some(Event::Key(KeyEvent {
+                    code: KeyCode::Esc, ..
+                })) => return Ok((Some(false), vec![])),
+                Some(ev) => keys.push(ev),
+                None => {
+                    return match bound {
+                        Some(f) => Ok((Some(f(None, None, out, state)?), vec![])),
+                        None => Ok((None, keys.split_off(1))),
+                    }
+                }
+            }
+        }
+    }
+
+    fn key(&mut self, ev: Event, out: &mut io::Stdout, state: &mut State) -> promkit::Result<bool> {
+        if self.listed.replace(false) {
+            listing::clear(out)?;
+        }
+        if self.hint.is_some() || std::mem::take(&mut self.usage_shown) {
+            queue!(out, Clear(ClearType::UntilNewLine))?;
+            out.flush()?;
+        }
+        // with any other key in between, the next Ctrl+C is a first one again
+        if !ctrl(&ev, 'c') {
+            self.last_ctrl_c = None;
+        }
+        let searching = self.search.is_some();
+        let pasted = std::mem::replace(&mut self.burst, event::poll(Duration::ZERO)?);
+        let (chord, rest) = match !searching && !pasted && self.starts_chord(&ev) {
+            true => self.chord(&ev, out, state)?,
+            false => (None, vec![]),
+        };
+        let exit = match ctrl(&ev, 'c') && !self.user.contains(&ev) {
+            _ if chord.is_some() => chord,
+            true => match self.ctrl_c(out, state) {
+                Ok(exit) => Some(exit),
+                Err(e) => {
+                    self.left(state);
+                    return Err(e);
+                }
+            },
+            false => self.search_key(&ev, out, state)?,
+        };
+        let exit = match exit {
+            Some(exit) => Some(exit),
+            None if searching => None,
+            None if pasted && self.hold_pastes && ev == enter() => {
+                state.0.editor.insert(Grapheme::from(PASTED_NEWLINE));
+                Some(false)
+            }
+            None => self.vi_key(&ev, out, state)?,
+        };
+        if self.search.is_some() {
+            return Ok(false);
+        }
+        let exit = match exit {
+            Some(exit) => exit,
+            None => match self.accept_hint(&ev) {
+                Some(n) => {
+                    for g in self.hint.iter().flat_map(|h| h[..n].iter()) {
+                        state.0.editor.insert(Grapheme::from(g.ch));
+                    }
+                    false
+                }
+                None => match self.bind.handle(ev, out, state) {
+                    Ok(exit) => exit,
+                    Err(e) => {
+                        self.left(state);
+                        return Err(e);
+                    }
+                },
+            },
+        };
+        self.hint = None;
+        match exit {
+            true => self.left(state),
+            false => {
+                self.mark_pastes(out, state)?;
+                self.highlight(out, state)?;
+                let after = self.show_hint(out, state)?;
+                self.show_rprompt(out, state, after)?;
+            }
+        }
+        // what was read for a chord that did not come about
+        for ev in rest.into_iter().take_while(|_| !exit) {
+            if self.key(ev, out, state)? {
+                return Ok(true);
+            }
+        }
+        Ok(exit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn press(code: KeyCode, modifiers: KeyModifiers) -> Event {
+        Event::Key(KeyEvent { code, modifiers })
+    }
+
+    #[test]
+    fn pasted_line_breaks_are_drawn_as_a_mark() {
+        let data = Graphemes::from(format!("build{}test", PASTED_NEWLINE));
+        assert_eq!(drawn(&data), "build↵test");
+        // in the place of the char it stands for
+        assert_eq!(Graphemes::from(drawn(&data)).width(), data.width());
+    }
+
+    // what conhost and Windows Terminal report, and what the bindings expect instead
+    #[test]
+    fn windows_keys_as_bound() {
+        let (none, shift, ctrl, alt) = (
+            KeyModifiers::NONE,
+            KeyModifiers::SHIFT,
+            KeyModifiers::CONTROL,
+            KeyModifiers::ALT,
+        );
+        let recorded = [
+            // Shift+Tab, with and without the console turning it into BackTab
+            (
+                press(KeyCode::BackTab, shift),
+                press(KeyCode::BackTab, none),
+            ),
+            (press(KeyCode::Tab, shift), press(KeyCode::BackTab, none)),
+            // Ctrl+A and Ctrl+R as the control characters
+            (
+                press(KeyCode::Char('\u{1}'), ctrl),
+                press(KeyCode::Char('a'), ctrl),
+            ),
+            (
+                press(KeyCode::Char('\u{12}'), none),
+                press(KeyCode::Char('r'), ctrl),
+            ),
+            // AltGr+Q on a German layout
+            (
+                press(KeyCode::Char('@'), ctrl | alt),
+                press(KeyCode::Char('@'), none),
+            ),
+            // a real Ctrl+Alt binding stays one
+            (
+                press(KeyCode::Char('x'), ctrl | alt),
+                press(KeyCode::Char('x'), ctrl | alt),
+            ),
+            (
+                press(KeyCode::Char('a'), none),
+                press(KeyCode::Char('a'), none),
+            ),
+        ];
+        for (sent, bound) in recorded {
+            assert_eq!(normalize(sent), bound, "{:?}", sent);
+        }
+    }
+
+    #[test]
+    fn typed_stand_in_is_not_a_line_break() {
+        assert_eq!(
+            normalize(press(KeyCode::Char(PASTED_NEWLINE), KeyModifiers::NONE)),
+            press(
+                KeyCode::Char(char::REPLACEMENT_CHARACTER),
+                KeyModifiers::NONE
+            )
+        );
+        // the arrow is typed like any other char now
+        assert_eq!(
+            normalize(press(KeyCode::Char('↵'), KeyModifiers::NONE)),
+            press(KeyCode::Char('↵'), KeyModifiers::NONE)
+        );
+    }
+
+    fn ctrl_key(c: char) -> KeyEvent {
+        KeyEvent {
+            code: KeyCode::Char(c),
+            modifiers: KeyModifiers::CONTROL,
+        }
+    }
+
+    #[test]
+    fn keys_are_named_like_emacs_does() {
+        let alt_f = KeyEvent {
+            code: KeyCode::Char('f'),
+            modifiers: KeyModifiers::ALT,
+        };
+        let names = [ctrl_key('x'), alt_f, KeyEvent::from(KeyCode::Enter)]
+            .map(|k| key_name(&Event::Key(k)));
+        assert_eq!(names, ["C-x", "M-f", "Enter"]);
+    }
+
+    #[test]
+    fn a_chord_waits_while_a_longer_one_may_follow() {
+        let f: Rc<EventHandleFn<State>> = Rc::new(|_, _, _, _| Ok(false));
+        let chord = |keys: Chord| keys.0.into_iter().map(Event::Key).collect::<Vec<_>>();
+        let chords = vec![
+            (chord([ctrl_key('x'), ctrl_key('s')].into()), f.clone()),
+            (
+                chord(vec![ctrl_key('x'), ctrl_key('c'), ctrl_key('c')].into()),
+                f,
+            ),
+        ];
+        let keys = chord([ctrl_key('x')].into());
+        let (bound, longer) = chord_lookup(&chords, &keys);
+        assert!(bound.is_none() && longer);
+        let (bound, longer) = chord_lookup(&chords, &chord([ctrl_key('x'), ctrl_key('s')].into()));
+        assert!(bound.is_some() && !longer);
+        let (bound, longer) = chord_lookup(&chords, &chord(ctrl_key('s').into()));
+        assert!(bound.is_none() && !longer);
+    }
+}
@@ -0,0 +1,290 @@
+// split a line the way shlex does, but tolerate the unfinished token being typed:
+// an unterminated quote yields its content so far, and a trailing space (or an
+// empty line) yields an empty token to complete
+pub(crate) fn split_partial(line: &str) -> Vec<String> {
+    let mut tokens = vec![];
+    let mut cur: Option<String> = None;
+    let mut chars = line.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            c if c.is_whitespace() => {
+                if let Some(t) = cur.take() {
+                    tokens.push(t);
+                }
+            }
+            '\'' => {
+                let t = cur.get_or_insert_with(String::new);
+                for c in chars.by_ref() {
+                    if c == '\'' {
+                        break;
+                    }
+                    t.push(c);
+                }
+            }
+            '"' => {
+                let t = cur.get_or_insert_with(String::new);
+                while let Some(c) = chars.next() {
+                    match c {
+                        '"' => break,
+                        '\\' => match chars.next() {
+                            Some(e @ ('$' | '`' | '"' | '\\')) => t.push(e),
+                            Some('\n') | None => {}
+                            Some(e) => {
+                                t.push('\\');
+                                t.push(e)
+                            }
+                        },
+                        c => t.push(c),
+                    }
+                }
+            }
+            '\\' => {
+                let t = cur.get_or_insert_with(String::new);
+                match chars.next() {
+                    Some('\n') | None => {}
+                    Some(e) => t.push(e),
+                }
+            }
+            c => cur.get_or_insert_with(String::new).push(c),
+        }
+    }
+    tokens.push(cur.unwrap_or_default());
+    tokens
+}
+
+// how a line leaves off, when not `Done` it goes on in the next one
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub(crate) enum Ends {
+    Done,
+    // with a backslash escaping the newline
+    Backslash,
+    // inside a quote that is still open
+    Quote,
+}
+
+// the chars of `line` that shlex would see outside quotes and escapes, with their
+// byte offsets, and how the line ends
+fn scan(line: &str) -> (Vec<(usize, char)>, Ends) {
+    let mut r = vec![];
+    let mut chars = line.char_indices();
+    while let Some((i, c)) = chars.next() {
+        match c {
+            '\'' => {
+                if chars.by_ref().all(|(_, c)| c != '\'') {
+                    return (r, Ends::Quote);
+                }
+            }
+            '"' => loop {
+                match chars.next() {
+                    Some((_, '"')) => break,
+                    Some((_, '\\')) => {
+                        chars.next();
+                    }
+                    Some(_) => {}
+                    None => return (r, Ends::Quote),
+                }
+            },
+            '\\' => {
+                if chars.next().is_none() {
+                    return (r, Ends::Backslash);
+                }
+            }
+            c => r.push((i, c)),
+        }
+    }
+    (r, Ends::Done)
+}
+
+// separators and comments can only start at one of these
+pub(crate) fn unquoted(line: &str) -> Vec<(usize, char)> {
+    scan(line).0
+}
+
+pub(crate) fn ends(line: &str) -> Ends {
+    scan(line).1
+}
+
+// `next` added to a line that went on; a newline stays in the open quote, the
+// escaped one of a backslash turns into a space
+pub(crate) fn join(line: &mut String, ends: Ends, next: &str) {
+    match ends {
+        Ends::Backslash => {
+            line.pop();
+            line.push(' ');
+        }
+        _ => line.push('\n'),
+    }
+    line.push_str(next);
+}
+
+// a line without its `#` comment, which like in shlex can only start a word
+pub(crate) fn strip_comment(line: &str) -> &str {
+    let starts_word = |i: usize| {
+        line[..i]
+            .chars()
+            .last()
+            .is_none_or(|p| p.is_whitespace() || p == ';')
+    };
+    match unquoted(line)
+        .into_iter()
+        .find(|&(i, c)| c == '#' && starts_word(i))
+    {
+        Some((i, _)) => &line[..i],
+        None => line,
+    }
+}
+
+// `line` split at every `sep` that is neither quoted nor escaped; from the first
+// piece `whole` claims on, e.g. one starting with a raw task, the rest of the line
+// stays together
+pub(crate) fn split_unquoted<F: Fn(&str) -> bool>(line: &str, sep: char, whole: F) -> Vec<&str> {
+    let mut r = vec![];
+    let mut start = 0;
+    for (i, _) in unquoted(line).into_iter().filter(|&(_, c)| c == sep) {
+        if whole(&line[start..i]) {
+            break;
+        }
+        r.push(&line[start..i]);
+        start = i + sep.len_utf8();
+    }
+    r.push(&line[start..]);
+    r
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Chain {
+    And,
+    Or,
+}
+
+// the commands of `a && b || c` with the operator before each, which is `None` for
+// the first; `None` if an operator is missing the command on one of its sides. Like
+// with `split_unquoted`, the command `whole` claims is the rest of the line
+pub(crate) fn split_chain<F: Fn(&str) -> bool>(
+    line: &str,
+    whole: F,
+) -> Option<Vec<(Option<Chain>, &str)>> {
+    let unquoted = unquoted(line);
+    let mut r = vec![];
+    let (mut start, mut op) = (0, None);
+    for w in unquoted.windows(2) {
+        let ((i, a), (j, b)) = (w[0], w[1]);
+        let next = match (a, b) {
+            ('&', '&') => Chain::And,
+            ('|', '|') => Chain::Or,
+            _ => continue,
+        };
+        // `a &&& b` or overlapping pairs
+        if j != i + 1 || i < start {
+            continue;
+        }
+        if whole(&line[start..i]) {
+            break;
+        }
+        r.push((op, line[start..i].trim()));
+        start = j + 1;
+        op = Some(next);
+    }
+    r.push((op, line[start..].trim()));
+    (r.len() == 1 || r.iter().all(|(_, c)| !c.is_empty())).then_some(r)
+}
+
+// the command of a line ending in an unquoted `&`, which is not the end of `&&`
+pub(crate) fn background(line: &str) -> Option<&str> {
+    let end = line.trim_end();
+    let i = end.len().checked_sub(1)?;
+    let unquoted = unquoted(line);
+    let amp = |i: usize| unquoted.iter().any(|&(j, c)| j == i && c == '&');
+    (amp(i) && !(i > 0 && amp(i - 1))).then(|| &line[..i])
+}
+
+// the line before an unquoted `--` word and everything after it exactly as typed
+pub(crate) fn split_raw(line: &str) -> (&str, Option<&str>) {
+    for w in unquoted(line).windows(2) {
+        let ((i, a), (j, b)) = (w[0], w[1]);
+        if a == '-' && b == '-' && j == i + 1 {
+            let before = line[..i].chars().last();
+            let after = line[j + 1..].chars().next();
+            if before.is_none_or(char::is_whitespace) && after.is_none_or(char::is_whitespace) {
+                return (&line[..i], Some(line[j + 1..].trim_start()));
+            }
+        }
+    }
+    (line, None)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn partial_tokens_keep_their_quotes_together() {
+        assert_eq!(
+            split_partial(r#"note add "my title" --t"#),
+            ["note", "add", "my title", "--t"]
+        );
+        // the open quote is the token being typed
+        assert_eq!(split_partial("note add 'my ti"), ["note", "add", "my ti"]);
+        assert_eq!(split_partial("note "), ["note", ""]);
+        assert_eq!(split_partial(""), [""]);
+        assert_eq!(split_partial(r"a\ b"), ["a b"]);
+    }
+
+    #[test]
+    fn comments_start_an_unquoted_word() {
+        assert_eq!(strip_comment("# all of it"), "");
+        assert_eq!(strip_comment("status # trailing"), "status ");
+        assert_eq!(
+            strip_comment(r##"echo "#notacomment""##),
+            r##"echo "#notacomment""##
+        );
+        assert_eq!(strip_comment("echo '#' # and"), "echo '#' ");
+        assert_eq!(strip_comment(r"echo \#1"), r"echo \#1");
+        assert_eq!(strip_comment("echo a#b"), "echo a#b");
+        assert_eq!(strip_comment("a;# b"), "a;");
+    }
+
+    #[test]
+    fn quoted_separators_do_not_split() {
+        assert_eq!(split_unquoted("a; b;c", ';', |_| false), ["a", " b", "c"]);
+        assert_eq!(
+            split_unquoted(r#"msg send "hello; world"; 'x;y'"#, ';', |_| false),
+            [r#"msg send "hello; world""#, " 'x;y'"]
+        );
+        assert_eq!(split_unquoted(r"a\; b", ';', |_| false), [r"a\; b"]);
+        assert_eq!(split_unquoted("", ';', |_| false), [""]);
+    }
+
+    #[test]
+    fn chains_split_outside_quotes() {
+        use Chain::*;
+        assert_eq!(
+            split_chain("build && deploy || notify", |_| false),
+            Some(vec![
+                (None, "build"),
+                (Some(And), "deploy"),
+                (Some(Or), "notify")
+            ])
+        );
+        assert_eq!(
+            split_chain("say 'a && b' || x", |_| false),
+            Some(vec![(None, "say 'a && b'"), (Some(Or), "x")])
+        );
+        assert_eq!(split_chain("a &&", |_| false), None);
+        assert_eq!(split_chain("|| a", |_| false), None);
+        assert_eq!(split_chain("", |_| false), Some(vec![(None, "")]));
+    }
+
+    #[test]
+    fn a_claimed_piece_takes_the_rest() {
+        let calc = |c: &str| c.trim_start().starts_with("calc");
+        assert_eq!(
+            split_unquoted("a; calc 1;2; b", ';', calc),
+            ["a", " calc 1;2; b"]
+        );
+        assert_eq!(
+            split_chain("a && calc x||y && z", calc),
+            Some(vec![(None, "a"), (Some(Chain::And), "calc x||y && z")])
+        );
+    }
+}
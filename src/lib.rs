@@ -1,49 +1,678 @@
 pub extern crate clap;
 pub extern crate promkit;
-use clap::{ArgMatches, Command};
-use std::collections::HashMap;
+use clap::{Arg, ArgAction, ArgMatches, Command, ValueHint};
+use std::cell::{Cell, OnceCell, RefCell};
+use std::collections::{HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, IsTerminal, Write};
+use std::ops::ControlFlow;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+mod alias;
+#[cfg(feature = "async")]
+mod async_task;
+mod builtins;
+mod cancel;
+mod completions;
+mod cycle;
+mod distance;
+mod edit;
+mod editor;
+mod error;
+mod feedback;
+mod fn_task;
+mod fuzzy;
+mod hint;
+mod history;
+mod idle;
+mod input;
+mod jobs;
+mod keys;
+mod lex;
+mod listing;
+mod matcher;
+mod pager;
+mod path;
+mod printer;
+mod raw;
+mod rprompt;
+mod script;
+mod search;
+mod shell;
+mod theme;
+mod transcript;
+mod vars;
+mod vi;
+
+#[cfg(feature = "async")]
+pub use async_task::{AsyncTask, TaskFuture};
+pub use cancel::CancelToken;
+pub use completions::{Candidate, Completions};
+use cycle::Cycle;
+pub use editor::EditedLines;
+pub use error::CliError;
+pub use feedback::NoMatchFeedback;
+pub use fn_task::StatefulTask;
+pub use hint::hint_completer;
+pub use idle::IdleAction;
+pub use input::{confirm, read_line, read_secret, sub_prompt, SubPrompt};
+use keys::Keys;
+pub use keys::{Chord, CtrlCBehavior};
+use matcher::Matcher;
+pub use matcher::{CompleteOptions, CompletionMode, CompletionOrder};
+pub use pager::page;
+pub use printer::PromptPrinter;
+pub use raw::RawTask;
+pub use script::{BatchLineReport, BatchOptions, BatchReport, ErrorPolicy};
+pub use search::SearchAccept;
+pub use theme::PromptTheme;
+pub use vars::UndefinedVariable;
+pub use vi::EditMode;
 
 use promkit::{
     buffer::Buffer,
     build::Builder,
     crossterm::event::{Event, KeyCode, KeyEvent, KeyModifiers},
-    crossterm::style::Color,
+    crossterm::queue,
+    crossterm::style::{Color, Print},
     grapheme::Graphemes,
     handler,
     keybind::KeyBind,
     readline::{self, State},
-    register::Register,
-    suggest::Suggest,
+    state::Render,
     EventHandleFn,
 };
 
-#[derive(Debug, Eq, PartialEq)]
+#[derive(Debug, Clone, Eq, PartialEq)]
 pub enum TaskAction {
     Continue,
     Break,
-    Exit,
+    // leave with the given process exit code
+    Exit(i32),
+    // the task failed, the message is shown like any other error
+    Error(String),
+}
+
+// what the process should exit with once the cli hands back `action`
+pub fn exit_code(action: &TaskAction) -> i32 {
+    match action {
+        TaskAction::Continue | TaskAction::Break => 0,
+        TaskAction::Exit(code) => *code,
+        TaskAction::Error(_) => 1,
+    }
+}
+
+// how `run_interactive` reads its lines
+#[derive(Debug, Clone, Copy, Default, Eq, PartialEq)]
+pub enum InputMode {
+    // the prompt when both stdin and stdout are a terminal, plain lines otherwise
+    #[default]
+    Auto,
+    Prompt,
+    // stdin line by line, nothing echoed, like a script piped in
+    Lines,
+}
+
+// so `fn main() -> Result<TaskAction, CliError>` exits with the task's code
+impl std::process::Termination for TaskAction {
+    fn report(self) -> std::process::ExitCode {
+        std::process::ExitCode::from(exit_code(&self) as u8)
+    }
 }
 
-pub trait Task {
+// `C` is what the cli is run with, see `Cli::run`; `()` unless the tasks share one
+pub trait Task<C = ()> {
     fn command(&self) -> Command;
-    fn action(&self, matches: &ArgMatches) -> TaskAction;
-    fn suggests(&self, args: &[&'_ str]) -> Option<Graphemes>;
+    fn action(&self, ctx: &C, matches: &ArgMatches) -> TaskAction;
+    // `raw` is what followed `--` on the line, untouched by quoting and expansion
+    fn action_raw(&self, ctx: &C, matches: &ArgMatches, raw: Option<&str>) -> TaskAction {
+        let _ = raw;
+        self.action(ctx, matches)
+    }
+    fn suggests(&self, args: &[&'_ str]) -> Option<Graphemes> {
+        complete_args(&self.command(), args)
+    }
+    fn candidates(&self, args: &[&'_ str]) -> Vec<Graphemes> {
+        args_candidates(&self.command(), args)
+    }
+    fn completions(&self, args: &[&'_ str]) -> Option<Completions> {
+        let mut cmd = self.command();
+        cmd.build();
+        let candidates = self
+            .candidates(args)
+            .into_iter()
+            .map(|text| Candidate {
+                description: describe(&cmd, &lex::split_partial(&text.to_string())),
+                text,
+            })
+            .collect::<Vec<_>>();
+        let replacement = self.suggests(args);
+        if replacement.is_none() && candidates.is_empty() {
+            return None;
+        }
+        Some(Completions {
+            replacement: replacement.unwrap_or_else(|| join_args(args)),
+            candidates,
+        })
+    }
+}
+
+// the matches of a task's command as the struct its args were declared with
+pub fn from_matches<T: clap::FromArgMatches>(matches: &ArgMatches) -> Result<T, CliError> {
+    T::from_arg_matches(matches).map_err(CliError::Clap)
 }
 
 pub fn complete<L: IntoIterator<Item = String>>(l: L, text: &str) -> Graphemes {
-    let mut s = Suggest::default();
-    s.register_all(l);
     let g = Graphemes::from(text);
-    s.search(&g).unwrap_or(g)
+    let m = Matcher::current();
+    // the common prefix must account for every match, not just the listed ones
+    let matched = m.sorted(l.into_iter().collect(), text);
+    if m.mode != CompletionMode::Prefix {
+        return matched.first().map_or(g, Graphemes::from);
+    }
+    matched
+        .into_iter()
+        .map(Graphemes::from)
+        .reduce(|a, b| a.longest_common_prefix(&b))
+        // candidates differing only in case inside the typed text
+        .filter(|r| r.len() >= g.len())
+        .unwrap_or(g)
+}
+
+pub fn complete_with<L: IntoIterator<Item = String>>(
+    l: L,
+    text: &str,
+    options: CompleteOptions,
+) -> Graphemes {
+    Matcher {
+        options,
+        ..Matcher::current()
+    }
+    .scope(|| complete(l, text))
+}
+
+// what reading the prompt came back with
+enum Input {
+    Line(String),
+    // nothing to run this time
+    Skip,
+    // Ctrl+D on an empty line
+    Eof,
+    // what the idle timeout ended up with
+    Action(TaskAction),
+}
+
+// what `Cli::resolve` made of a line
+enum Resolved<'a> {
+    // run already, or no command at all
+    Done(Result<TaskAction, CliError>),
+    Repeat(usize, &'a str),
+    // the task's args, and what followed `--`
+    Args(Vec<String>, Option<&'a str>),
+}
+
+// the commands the cli runs itself rather than the task registered for them
+enum Own {
+    Source,
+    Repeat(usize, String),
+}
+
+// a task's action under way, the after hooks get its time
+struct Started {
+    at: Instant,
+    _themed: input::Themed,
+}
+
+// the next command of a batch, or where its script is broken
+enum Next {
+    Run(usize, String),
+    Broken(usize, CliError),
+}
+
+fn find_flag<'a>(cmd: &'a Command, token: &str) -> Option<&'a Arg> {
+    match token.strip_prefix("--") {
+        Some(l) => {
+            let l = l.split('=').next().unwrap_or_default();
+            cmd.get_arguments().find(|a| {
+                a.get_long_and_visible_aliases()
+                    .is_some_and(|ls| ls.contains(&l))
+            })
+        }
+        None => token
+            .strip_prefix('-')
+            .and_then(|s| s.chars().last())
+            .and_then(|c| {
+                cmd.get_arguments().find(|a| {
+                    a.get_short_and_visible_aliases()
+                        .is_some_and(|ss| ss.contains(&c))
+                })
+            }),
+    }
+}
+
+fn is_flag(token: &str) -> bool {
+    token.len() > 1 && token.starts_with('-')
+}
+
+// the flag that is still waiting for its value, if any
+fn pending_flag<'a>(cmd: &'a Command, token: &str) -> Option<&'a Arg> {
+    if !is_flag(token) || token.contains('=') {
+        return None;
+    }
+    find_flag(cmd, token).filter(|a| a.get_action().takes_values())
+}
+
+fn possible_values(a: &Arg) -> Vec<String> {
+    a.get_possible_values()
+        .iter()
+        .filter(|v| !v.is_hide_set())
+        .map(|v| v.get_name().to_string())
+        .collect()
+}
+
+fn flag_candidates(cmd: &Command, consumed: &[&str], last: &str) -> Vec<String> {
+    let used = |a: &Arg| {
+        !matches!(a.get_action(), ArgAction::Append | ArgAction::Count)
+            && consumed.iter().any(|t| {
+                is_flag(t)
+                    && match t.strip_prefix("--") {
+                        Some(_) => find_flag(cmd, t).is_some_and(|f| f.get_id() == a.get_id()),
+                        None => t[1..].chars().any(|c| {
+                            a.get_short_and_visible_aliases()
+                                .is_some_and(|ss| ss.contains(&c))
+                        }),
+                    }
+            })
+    };
+    let longs = cmd
+        .get_arguments()
+        .filter(|a| !used(a))
+        .filter_map(|a| a.get_long())
+        .map(|l| format!("--{}", l));
+    if last.starts_with("--") {
+        longs.collect()
+    } else {
+        cmd.get_arguments()
+            .filter(|a| !used(a))
+            .filter_map(|a| a.get_short())
+            .map(|s| format!("-{}", s))
+            .chain(longs)
+            .collect()
+    }
+}
+
+fn value_candidates(a: &Arg, token: &str) -> Vec<String> {
+    let values = possible_values(a);
+    if !values.is_empty() {
+        return values;
+    }
+    hint_completer(a.get_value_hint(), token)
+}
+
+// indices of the tokens that are not flags or flag values
+fn positionals(cmd: &Command, consumed: &[&str]) -> Vec<usize> {
+    let mut r = vec![];
+    let mut tokens = consumed.iter().enumerate();
+    while let Some((i, t)) = tokens.next() {
+        if is_flag(t) {
+            if pending_flag(cmd, t).is_some() {
+                tokens.next();
+            }
+        } else {
+            r.push(i);
+        }
+    }
+    r
+}
+
+// the arg whose value is being typed after `consumed`
+fn value_arg<'a>(cmd: &'a Command, consumed: &[&str]) -> Option<&'a Arg> {
+    if let Some(a) = consumed.last().and_then(|t| pending_flag(cmd, t)) {
+        return Some(a);
+    }
+    cmd.get_positionals()
+        .nth(positionals(cmd, consumed).len())
+        .or_else(|| {
+            cmd.get_positionals()
+                .last()
+                .filter(|a| a.get_num_args().is_some_and(|n| n.max_values() > 1))
+        })
+}
+
+fn subcommand_candidates(cmd: &Command) -> Vec<String> {
+    cmd.get_subcommands()
+        .filter(|c| !c.is_hide_set())
+        .flat_map(|c| [c.get_name()].into_iter().chain(c.get_all_aliases()))
+        .map(|c| c.to_string())
+        .collect()
+}
+
+// make a token survive `shlex::split` as a single argument
+fn quote(s: String) -> String {
+    if s.contains(|c: char| c.is_whitespace() || matches!(c, '\'' | '"' | '\\'))
+        || s.starts_with('#')
+    {
+        shlex::try_quote(&s).map_or(s.clone(), |q| q.to_string())
+    } else {
+        s
+    }
+}
+
+fn split_at_cursor(line: &str, cursor: usize) -> (&str, &str) {
+    let at = line
+        .char_indices()
+        .nth(cursor)
+        .map_or(line.len(), |(i, _)| i);
+    line.split_at(at)
+}
+
+// the rest of the line goes to the task as it is, nothing to complete there
+fn after_raw(consumed: &[&str]) -> bool {
+    consumed.contains(&"--")
+}
+
+// overrides the candidates for an arg's value, `None` falls back to the Command
+type ValueSource<'a> = dyn Fn(&Arg, &str) -> Option<Vec<String>> + 'a;
+
+// split `args` into the untouched head and the candidates for the last token
+fn args_completion(
+    cmd: &Command,
+    args: &[&'_ str],
+    values: &ValueSource,
+) -> Option<(Vec<String>, Vec<String>)> {
+    let (last, consumed) = args.split_last()?;
+    if after_raw(consumed) {
+        return None;
+    }
+    let mut cmd = cmd.clone();
+    cmd.build();
+    let taken = positionals(&cmd, consumed);
+    if cmd.has_subcommands() {
+        // descend into the first subcommand named on the line, the tokens before it
+        // can only be values of this command's own positionals
+        if let Some(&i) = taken
+            .iter()
+            .find(|&&i| cmd.find_subcommand(consumed[i]).is_some())
+        {
+            let sub = cmd.find_subcommand(consumed[i])?;
+            let (mut head, candidates) = args_completion(sub, &args[i + 1..], values)?;
+            head.splice(0..0, args[..=i].iter().map(|s| s.to_string()));
+            return Some((head, candidates));
+        }
+        if taken.len() > cmd.get_positionals().count() {
+            return None;
+        }
+    }
+    let candidates = if let Some((flag, value)) = last.split_once('=').filter(|_| is_flag(last)) {
+        // `--flag=value` and `-f=value`, the value is completed in place
+        let a = find_flag(&cmd, flag).filter(|a| a.get_action().takes_values())?;
+        values(a, value)
+            .unwrap_or_else(|| value_candidates(a, value))
+            .into_iter()
+            .map(|v| format!("{}={}", flag, v))
+            .collect()
+    } else if is_flag(last) || *last == "-" {
+        flag_candidates(&cmd, consumed, last)
+    } else {
+        let pending = consumed.last().and_then(|t| pending_flag(&cmd, t));
+        let mut candidates = value_arg(&cmd, consumed)
+            .map(|a| values(a, last).unwrap_or_else(|| value_candidates(a, last)))
+            .unwrap_or_default();
+        if pending.is_none() {
+            if taken.len() <= cmd.get_positionals().count() {
+                candidates.extend(subcommand_candidates(&cmd));
+            }
+            // nothing typed yet for the next token, anything may follow
+            if last.is_empty() {
+                candidates.extend(flag_candidates(&cmd, consumed, last));
+            }
+        }
+        if candidates.is_empty() {
+            return None;
+        }
+        candidates
+    };
+    Some((
+        consumed.iter().map(|s| s.to_string()).collect(),
+        Matcher::current().rank(candidates, last),
+    ))
+}
+
+// what the last token of a completed line stands for: a subcommand's about,
+// a flag's help or a possible value's help; `cmd` must be built already
+fn describe<S: AsRef<str>>(cmd: &Command, tokens: &[S]) -> Option<String> {
+    let tokens = tokens.iter().map(|t| t.as_ref()).collect::<Vec<_>>();
+    let (last, consumed) = tokens.split_last()?;
+    let taken = positionals(cmd, consumed);
+    if let Some(&i) = taken
+        .iter()
+        .find(|&&i| cmd.find_subcommand(consumed[i]).is_some())
+    {
+        return describe(cmd.find_subcommand(consumed[i])?, &tokens[i + 1..]);
+    }
+    let value_help = |a: &Arg, value: &str| {
+        a.get_possible_values()
+            .into_iter()
+            .find(|v| v.matches(value, false))
+            .and_then(|v| v.get_help().map(|h| h.to_string()))
+    };
+    if let Some(sub) = cmd.find_subcommand(last) {
+        sub.get_about().map(|h| h.to_string())
+    } else if let Some((flag, value)) = last.split_once('=').filter(|_| is_flag(last)) {
+        value_help(find_flag(cmd, flag)?, value)
+    } else if is_flag(last) {
+        find_flag(cmd, last)?.get_help().map(|h| h.to_string())
+    } else {
+        value_help(value_arg(cmd, consumed)?, last)
+    }
+}
+
+// the part of `cmd`'s usage the `args` typed so far leave open; `cmd` must be built
+fn missing_usage(cmd: &Command, args: &[&str]) -> Option<String> {
+    if after_raw(args) {
+        return None;
+    }
+    let taken = positionals(cmd, args);
+    if let Some(&i) = taken
+        .iter()
+        .find(|&&i| cmd.find_subcommand(args[i]).is_some())
+    {
+        return missing_usage(cmd.find_subcommand(args[i])?, &args[i + 1..]);
+    }
+    let placeholder = |a: &Arg| {
+        a.get_value_names()
+            .and_then(|n| n.first())
+            .map_or_else(|| a.get_id().as_str().to_uppercase(), |n| n.to_string())
+    };
+    let given = |a: &Arg| {
+        args.iter()
+            .filter(|t| is_flag(t))
+            .any(|t| find_flag(cmd, t).is_some_and(|f| f.get_id() == a.get_id()))
+    };
+    let optional = |text: String, required: bool| match required {
+        true => text,
+        false => format!("[{}]", text),
+    };
+    let mut parts = vec![];
+    for a in cmd
+        .get_positionals()
+        .skip(taken.len())
+        .filter(|a| !a.is_hide_set())
+    {
+        let mut name = placeholder(a);
+        if a.get_num_args().is_some_and(|n| n.max_values() > 1) {
+            name.push_str("...");
+        }
+        parts.push(match a.is_required_set() {
+            true => format!("<{}>", name),
+            false => format!("[{}]", name),
+        });
+    }
+    for a in cmd.get_opts().chain(
+        cmd.get_arguments()
+            .filter(|a| !a.is_positional() && !a.get_action().takes_values()),
+    ) {
+        if a.is_hide_set() || given(a) || ["help", "version"].contains(&a.get_id().as_str()) {
+            continue;
+        }
+        let flag = match (a.get_long(), a.get_short()) {
+            (Some(l), _) => format!("--{}", l),
+            (None, Some(s)) => format!("-{}", s),
+            (None, None) => continue,
+        };
+        let text = match a.get_action().takes_values() {
+            true => format!("{} <{}>", flag, placeholder(a)),
+            false => flag,
+        };
+        parts.push(optional(text, a.is_required_set()));
+    }
+    if cmd.has_subcommands() && taken.is_empty() {
+        parts.push(optional(
+            "<COMMAND>".to_string(),
+            cmd.is_subcommand_required_set(),
+        ));
+    }
+    (!parts.is_empty()).then(|| parts.join(" "))
 }
 
-pub struct Cli {
+fn join_args(args: &[&str]) -> Graphemes {
+    Graphemes::from(
+        args.iter()
+            .map(|a| quote(a.to_string()))
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+fn join_replacement(head: Vec<String>, candidates: Vec<String>, last: &str) -> Graphemes {
+    Graphemes::from(
+        head.into_iter()
+            .chain([complete(candidates, last).to_string()])
+            .map(quote)
+            .collect::<Vec<_>>()
+            .join(" "),
+    )
+}
+
+fn join_candidates(head: Vec<String>, candidates: Vec<String>) -> Vec<Graphemes> {
+    candidates
+        .into_iter()
+        .map(|c| {
+            Graphemes::from(
+                head.iter()
+                    .cloned()
+                    .chain([c])
+                    .map(quote)
+                    .collect::<Vec<_>>()
+                    .join(" "),
+            )
+        })
+        .collect()
+}
+
+pub fn complete_args(cmd: &Command, args: &[&'_ str]) -> Option<Graphemes> {
+    let (head, candidates) = args_completion(cmd, args, &|_, _| None)?;
+    Some(join_replacement(head, candidates, args.last()?))
+}
+
+pub fn args_candidates(cmd: &Command, args: &[&'_ str]) -> Vec<Graphemes> {
+    args_completion(cmd, args, &|_, _| None)
+        .map(|(head, candidates)| join_candidates(head, candidates))
+        .unwrap_or_default()
+}
+
+pub type Completer = Box<dyn Fn(&str) -> Vec<String> + Send + Sync>;
+pub type SuggestProvider = Box<dyn Fn(&[&str]) -> Option<Graphemes> + Send + Sync>;
+pub type BeforeHook = Box<dyn Fn(&str, &ArgMatches) -> Result<(), String> + Send + Sync>;
+pub type AfterHook = Box<dyn Fn(&str, &TaskAction, Duration) + Send + Sync>;
+pub type Fallback = Box<dyn Fn(&str) -> Result<TaskAction, String> + Send + Sync>;
+pub type LabelFn = Box<dyn Fn() -> String + Send + Sync>;
+pub type ErrorSink = Box<dyn Fn(&CliError) + Send + Sync>;
+pub type FarewellFn = Box<dyn Fn(&TaskAction) -> String + Send + Sync>;
+
+pub struct Cli<C = ()> {
     cmd: Command,
-    cmds: HashMap<String, Box<dyn Task + 'static>>,
+    cmds: HashMap<String, Box<dyn Task<C> + 'static>>,
+    // task names in registration order, so listings and help are stable
+    order: Vec<String>,
+    // the task each alias of a command stands for
+    canonical: HashMap<String, String>,
+    // the names the crate's own builtins took, which they may take again
+    builtins: HashSet<String>,
+    // built on first use, every Tab would otherwise rebuild them from all the tasks
+    names: OnceCell<Vec<String>>,
+    root: OnceCell<Command>,
+    completion_rows: usize,
+    matcher: Matcher,
+    completers: HashMap<(String, String), Completer>,
+    hint_sources: HashMap<ValueHint, Completer>,
+    providers: HashMap<String, SuggestProvider>,
+    history_hints: bool,
+    usage_hints: bool,
+    word_chars: String,
+    edit_mode: EditMode,
+    hold_pastes: bool,
+    ctrl_c: CtrlCBehavior,
+    double_ctrl_c: Option<Duration>,
+    bindings: Vec<(Chord, Rc<EventHandleFn<State>>)>,
+    chord_timeout: Duration,
+    edited_lines: EditedLines,
+    highlight: bool,
+    prompt_label: Option<String>,
+    label_fn: Option<LabelFn>,
+    // the label the prompt showed for the line being run, which the transcript records
+    shown_label: RefCell<Option<String>>,
+    rprompt: Option<rprompt::RightLabel>,
+    banner: Option<LabelFn>,
+    farewell: Option<FarewellFn>,
+    input_mode: InputMode,
+    printer: PromptPrinter,
+    auto_confirm: Option<bool>,
+    idle: Option<(Duration, IdleAction)>,
+    transcript: Option<PathBuf>,
+    // the args of each task whose values never get written down
+    secrets: HashMap<String, Vec<String>>,
+    theme: PromptTheme,
+    search_accept: SearchAccept,
+    no_match: NoMatchFeedback,
+    abbreviations: bool,
+    variable_names: Completer,
+    vars: vars::Variables,
+    expand_vars: bool,
+    undefined: UndefinedVariable,
+    rc: Option<PathBuf>,
+    rc_strict: bool,
+    raw: HashMap<String, Rc<dyn RawTask>>,
+    aliases: alias::Aliases,
+    history: history::SharedHistory,
+    shell_escape: bool,
+    fallback: Option<Fallback>,
+    error_sink: Option<ErrorSink>,
+    cancel: CancelToken,
+    // the tasks that can also run with `&`
+    send: HashMap<String, Arc<dyn Task + Send + Sync>>,
+    jobs: jobs::SharedJobs,
+    #[cfg(feature = "async")]
+    async_tasks: HashMap<String, Rc<dyn AsyncTask<C>>>,
+    // how many `source` are running inside each other
+    source_depth: Cell<usize>,
+    before: Vec<BeforeHook>,
+    after: Vec<AfterHook>,
 }
 
 impl Cli {
     pub fn new(name: impl Into<clap::builder::Str>) -> Self {
+        Cli::for_context(name)
+    }
+}
+
+impl<C: 'static> Cli<C> {
+    // a cli whose tasks are handed a `&C` by every run, e.g. a device handle or a
+    // database pool: `Cli::<Device>::for_context("dev")`
+    pub fn for_context(name: impl Into<clap::builder::Str>) -> Self {
         // strip out usage
         const PARSER_TEMPLATE: &str = "\
         {all-args}
@@ -57,141 +686,3060 @@ impl Cli {
                 .subcommand_help_heading("Commands")
                 .help_template(PARSER_TEMPLATE),
             cmds: HashMap::new(),
+            order: vec![],
+            canonical: HashMap::new(),
+            builtins: HashSet::new(),
+            names: OnceCell::new(),
+            root: OnceCell::new(),
+            completion_rows: 10,
+            matcher: Matcher::default(),
+            completers: HashMap::new(),
+            hint_sources: HashMap::new(),
+            providers: HashMap::new(),
+            history_hints: false,
+            usage_hints: false,
+            word_chars: edit::WORD_CHARS.to_string(),
+            edit_mode: EditMode::Emacs,
+            hold_pastes: true,
+            ctrl_c: CtrlCBehavior::ClearLine,
+            double_ctrl_c: None,
+            bindings: Vec::new(),
+            chord_timeout: Duration::from_secs(1),
+            edited_lines: EditedLines::default(),
+            highlight: false,
+            prompt_label: None,
+            label_fn: None,
+            shown_label: RefCell::new(None),
+            rprompt: None,
+            banner: None,
+            farewell: None,
+            input_mode: InputMode::Auto,
+            printer: PromptPrinter::default(),
+            auto_confirm: None,
+            idle: None,
+            transcript: None,
+            secrets: HashMap::new(),
+            theme: PromptTheme::default(),
+            search_accept: SearchAccept::Edit,
+            no_match: NoMatchFeedback::default(),
+            abbreviations: false,
+            variable_names: Box::new(|_| std::env::vars().map(|(k, _)| k).collect()),
+            vars: vars::Variables::default(),
+            expand_vars: false,
+            undefined: UndefinedVariable::default(),
+            rc: None,
+            rc_strict: false,
+            raw: HashMap::new(),
+            aliases: Rc::new(RefCell::new(HashMap::new())),
+            history: Rc::new(RefCell::new(history::History {
+                limit: Some(1000),
+                skip_blank: true,
+                ..Default::default()
+            })),
+            shell_escape: false,
+            fallback: None,
+            error_sink: None,
+            cancel: CancelToken::default(),
+            send: HashMap::new(),
+            jobs: Rc::default(),
+            #[cfg(feature = "async")]
+            async_tasks: HashMap::new(),
+            source_depth: Cell::new(0),
+            before: vec![],
+            after: vec![],
         }
     }
 
-    pub fn add_task<T: Task + 'static>(mut self, t: T) -> Self {
-        self.cmds
-            .insert(t.command().get_name().to_string(), Box::new(t));
+    pub fn completion_rows(mut self, rows: usize) -> Self {
+        self.completion_rows = rows;
         self
     }
 
-    pub fn parse(&self, line: &str) -> Result<Option<ArgMatches>, String> {
-        let args = shlex::split(line).ok_or("error: Invalid quoting")?;
-        if args.is_empty() {
-            return Ok(None);
-        }
-        let cmd = self.command();
-        cmd.try_get_matches_from(args)
-            .map(|r| Some(r))
-            .map_err(|e| e.to_string())
+    pub fn completion_case_insensitive(mut self, on: bool) -> Self {
+        self.matcher.case_insensitive = on;
+        self
     }
 
-    pub fn run(&self, line: &str) -> Result<TaskAction, String> {
-        let matches = match self.parse(line)? {
-            None => return Ok(TaskAction::Continue),
-            Some(m) => m,
-        };
-        Ok(self.action(&matches))
+    pub fn completion_mode(mut self, mode: CompletionMode) -> Self {
+        self.matcher.mode = mode;
+        self
     }
 
-    pub fn prompt(self: &std::sync::Arc<Self>) -> readline::Builder {
-        let mut b = KeyBind::default();
-        let cli = self.clone();
-        b.assign(vec![
-            (
-                Event::Key(KeyEvent {
-                    code: KeyCode::Tab,
-                    modifiers: KeyModifiers::NONE,
-                }),
-                Box::new({
-                    move |_, _, _: &mut std::io::Stdout, state: &mut State| {
-                        let line = state.0.editor.data.to_string();
-                        let line = line.split_whitespace().collect::<Vec<_>>();
-                        if let Some(r) = cli.suggests(&line) {
-                            state.0.editor.replace(&r)
-                        }
-                        Ok(false)
-                    }
-                }) as Box<EventHandleFn<State>>,
-            ),
-            (
-                Event::Key(KeyEvent {
-                    code: KeyCode::Char('c'),
-                    modifiers: KeyModifiers::CONTROL,
-                }),
-                Box::new(|_, _, out: &mut std::io::Stdout, state: &mut State| {
-                    state.0.editor = Box::new(Buffer::default());
-                    handler::enter()(None, None, out, state)
-                }) as Box<EventHandleFn<State>>,
-            ),
-        ]);
+    pub fn register_completer<F: Fn(&str) -> Vec<String> + Send + Sync + 'static>(
+        mut self,
+        task: &str,
+        arg: &str,
+        f: F,
+    ) -> Self {
+        self.completers
+            .insert((task.to_string(), arg.to_string()), Box::new(f));
+        self
+    }
 
-        readline::Builder::default().handler(b)
+    // takes over `suggests` of the task, for completions made of data only known at runtime
+    pub fn suggest_provider<F: Fn(&[&str]) -> Option<Graphemes> + Send + Sync + 'static>(
+        mut self,
+        task: &str,
+        f: F,
+    ) -> Self {
+        self.providers.insert(task.to_string(), Box::new(f));
+        self
     }
 
-    pub fn run_batch(&self, cmd: &str) -> Result<(), String> {
-        for line in cmd
-            .split('\n')
-            .map(|s| s.trim())
-            .flat_map(|s| s.split(';').map(|s| s.trim()))
-        {
-            self.run(line)?;
-        }
-        Ok(())
+    pub fn hint_source<F: Fn(&str) -> Vec<String> + Send + Sync + 'static>(
+        mut self,
+        hint: ValueHint,
+        f: F,
+    ) -> Self {
+        self.hint_sources.insert(hint, Box::new(f));
+        self
     }
 
-    pub fn run_interactive_with<F: Fn(readline::Builder) -> readline::Builder>(
-        self: &std::sync::Arc<Self>,
+    // runs before each task with its name and matches, an error keeps the task from running
+    pub fn on_before<F: Fn(&str, &ArgMatches) -> Result<(), String> + Send + Sync + 'static>(
+        mut self,
         f: F,
-    ) -> Result<TaskAction, String> {
-        let mut prompt = f(self.prompt()).build().map_err(|e| e.to_string())?;
-        loop {
-            let line = prompt.run().map_err(|e| e.to_string())?;
-            let action = self.run(&line).unwrap_or_else(|e| {
-                println!("{}", e);
-                TaskAction::Continue
-            });
-            if action != TaskAction::Continue {
-                break Ok(action);
-            }
+    ) -> Self {
+        self.before.push(Box::new(f));
+        self
+    }
+
+    pub fn on_after<F: Fn(&str, &TaskAction, Duration) + Send + Sync + 'static>(
+        mut self,
+        f: F,
+    ) -> Self {
+        self.after.push(Box::new(f));
+        self
+    }
+
+    // takes the lines that do not start with a known command, as they were typed
+    pub fn fallback<F: Fn(&str) -> Result<TaskAction, String> + Send + Sync + 'static>(
+        mut self,
+        f: F,
+    ) -> Self {
+        self.fallback = Some(Box::new(f));
+        self
+    }
+
+    pub fn history_hints(mut self, on: bool) -> Self {
+        self.history_hints = on;
+        self
+    }
+
+    // the arguments a task still wants, dim after the line when there is no history hint
+    pub fn usage_hints(mut self, on: bool) -> Self {
+        self.usage_hints = on;
+        self
+    }
+
+    // `name> ` unless set
+    pub fn prompt_label(mut self, label: impl Into<String>) -> Self {
+        self.prompt_label = Some(label.into());
+        self
+    }
+
+    // asked for the label before every line, over any other label
+    pub fn prompt_label_fn<F: Fn() -> String + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.label_fn = Some(Box::new(f));
+        self
+    }
+
+    // drawn at the right edge of the prompt's row after every key, and gone while the line
+    // would reach it
+    pub fn rprompt_fn<F: Fn() -> String + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.rprompt = Some(std::sync::Arc::new(f));
+        self
+    }
+
+    // for other threads to print without breaking into the line being typed
+    pub fn printer(&self) -> PromptPrinter {
+        self.printer.clone()
+    }
+
+    // what `confirm` answers in a batch or with stdin piped, each question's default if `None`
+    pub fn auto_confirm(mut self, answer: Option<bool>) -> Self {
+        self.auto_confirm = answer;
+        self
+    }
+
+    // after `timeout` without a key at the prompt
+    pub fn idle_timeout(mut self, timeout: Duration, action: IdleAction) -> Self {
+        self.idle = Some((timeout, action));
+        self
+    }
+
+    pub fn input_mode(mut self, mode: InputMode) -> Self {
+        self.input_mode = mode;
+        self
+    }
+
+    // printed once above the first prompt, not for plain lines
+    pub fn banner(self, text: impl Into<String>) -> Self {
+        let text = text.into();
+        self.banner_fn(move || text.clone())
+    }
+
+    pub fn banner_fn<F: Fn() -> String + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.banner = Some(Box::new(f));
+        self
+    }
+
+    // printed once the interactive loop is left, told how
+    pub fn farewell<F: Fn(&TaskAction) -> String + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.farewell = Some(Box::new(f));
+        self
+    }
+
+    // takes the errors the crate would print, to log them or keep them instead
+    pub fn error_sink<F: Fn(&CliError) + Send + Sync + 'static>(mut self, f: F) -> Self {
+        self.error_sink = Some(Box::new(f));
+        self
+    }
+
+    pub fn theme(mut self, theme: PromptTheme) -> Self {
+        self.theme = theme;
+        self
+    }
+
+    pub fn prompt_color(mut self, color: Color) -> Self {
+        self.theme.label = color;
+        self
+    }
+
+    // the color of the history hints, dim unless set
+    pub fn suggest_color(mut self, color: Color) -> Self {
+        self.theme.hint = Some(color);
+        self
+    }
+
+    // what Enter does in a Ctrl+R search
+    pub fn search_accept(mut self, accept: SearchAccept) -> Self {
+        self.search_accept = accept;
+        self
+    }
+
+    // the first word in green when it names a command, red when it does not
+    pub fn highlight_command(mut self, on: bool) -> Self {
+        self.highlight = on;
+        self
+    }
+
+    // what counts as part of a word besides letters and digits, `-_/.` unless set
+    pub fn word_chars(mut self, chars: impl Into<String>) -> Self {
+        self.word_chars = chars.into();
+        self
+    }
+
+    pub fn edit_mode(mut self, mode: EditMode) -> Self {
+        self.edit_mode = mode;
+        self
+    }
+
+    // a pasted block with line breaks waits for Enter and then runs like a batch,
+    // unless this is off and every pasted line runs as it comes in
+    pub fn hold_pastes(mut self, on: bool) -> Self {
+        self.hold_pastes = on;
+        self
+    }
+
+    // what Ctrl+C does at the prompt; while a task runs it always cancels it
+    pub fn ctrl_c(mut self, behavior: CtrlCBehavior) -> Self {
+        self.ctrl_c = behavior;
+        self
+    }
+
+    // how soon a second Ctrl+C at the prompt leaves it, e.g. 2s; unless set it never does
+    pub fn double_ctrl_c_exit(mut self, within: Option<Duration>) -> Self {
+        self.double_ctrl_c = within;
+        self
+    }
+
+    // a key of the prompt, or a chord of keys pressed one after the other, taking over
+    // from what the crate binds it to
+    pub fn bind_key(mut self, keys: impl Into<Chord>, f: Box<EventHandleFn<State>>) -> Self {
+        let keys = keys.into();
+        if !keys.0.is_empty() {
+            self.bindings.push((keys, Rc::from(f)));
         }
+        self
     }
 
-    pub fn run_interactive(self: &std::sync::Arc<Self>) -> Result<TaskAction, String> {
-        self.run_interactive_with(|b| {
-            b.label(&format!("{}> ", self.cmd.get_name()))
-                .label_color(Color::Reset)
-                .limit_history_size(3)
-        })
+    // how long a chord's next key is waited for before its first counts on its own
+    pub fn chord_timeout(mut self, timeout: Duration) -> Self {
+        self.chord_timeout = timeout;
+        self
     }
-}
 
-impl Task for Cli {
-    fn command(&self) -> Command {
-        // strip out name/version
-        const APPLET_TEMPLATE: &str = "\
-                {about-with-newline}\n\
-                {usage-heading}\n    {usage}\n\
-                \n\
-                {all-args}{after-help}\
-            ";
-        self.cmds.values().fold(self.cmd.clone(), |c, t| {
-            c.subcommand(t.command().help_template(APPLET_TEMPLATE))
-        })
+    // what the lines of a command edited with Ctrl+X Ctrl+E come back as
+    pub fn edited_lines(mut self, lines: EditedLines) -> Self {
+        self.edited_lines = lines;
+        self
     }
-    fn action(&self, matches: &ArgMatches) -> TaskAction {
-        let (name, matches) = matches.subcommand().unwrap();
-        self.cmds[name].action(&matches)
+
+    pub fn no_match_feedback(mut self, feedback: NoMatchFeedback) -> Self {
+        self.no_match = feedback;
+        self
     }
-    fn suggests(&self, args: &[&'_ str]) -> Option<Graphemes> {
-        args.iter().next().map(|a| {
-            self.cmds
-                .get(*a)
-                .map(|c| c.suggests(&args[1..]))
-                .flatten()
-                .map_or_else(
-                    || {
-                        complete(
-                            self.cmds
-                                .keys()
-                                .map(move |s| s.to_string())
-                                .chain(["help".to_string()]),
-                            *a,
-                        )
-                    },
-                    |r| Graphemes::from([*a, &r.to_string()].join(" ")),
-                )
-        })
+
+    pub fn allow_abbreviations(mut self, on: bool) -> Self {
+        self.abbreviations = on;
+        self
+    }
+
+    // the variable names offered after `$`, the process environment by default
+    pub fn variable_source<F: Fn(&str) -> Vec<String> + Send + Sync + 'static>(
+        mut self,
+        f: F,
+    ) -> Self {
+        self.variable_names = Box::new(f);
+        self
+    }
+
+    // expands `$NAME` in every line and adds the `set` and `unset` builtins
+    pub fn variables(mut self, on: bool) -> Self {
+        self.expand_vars = on;
+        if on {
+            let vars = self.vars.clone();
+            self = self
+                .builtin(builtins::Set(vars.clone()))
+                .builtin(builtins::Unset(vars));
+        }
+        self
+    }
+
+    // `alias NAME WORDS...` shortcuts, expanded in place of the first word of a command
+    pub fn aliases(mut self, on: bool) -> Self {
+        if on {
+            let aliases = self.aliases.clone();
+            self = self
+                .builtin(builtins::Alias(aliases.clone()))
+                .builtin(builtins::Unalias(aliases));
+        }
+        self
+    }
+
+    // `source PATH` runs a script against this cli, its variables and aliases included
+    pub fn allow_source(mut self, on: bool) -> Self {
+        if on && !self.builtins.contains("source") {
+            self = self.builtin(builtins::Source);
+        }
+        self
+    }
+
+    // `repeat N COMMAND...` runs the rest of its command N times, as it was typed
+    pub fn allow_repeat(mut self, on: bool) -> Self {
+        if on && !self.repeats() {
+            self = self.builtin(builtins::Repeat);
+        }
+        self
+    }
+
+    // `sleep 500ms`, which Ctrl+C cuts short at the prompt
+    pub fn allow_sleep(self, on: bool) -> Self {
+        match on {
+            true => {
+                let cancel = self.cancel.clone();
+                self.builtin(builtins::Sleep(cancel))
+            }
+            false => self,
+        }
+    }
+
+    // `history`, listing what was entered at the prompt
+    pub fn allow_history(self, on: bool) -> Self {
+        match on {
+            true => {
+                let history = self.history.clone();
+                self.builtin(builtins::History(history))
+            }
+            false => self,
+        }
+    }
+
+    pub fn allow_clear(self, on: bool) -> Self {
+        match on {
+            true => self.builtin(builtins::Clear),
+            false => self,
+        }
+    }
+
+    pub fn with_builtins(self) -> Self {
+        self.variables(true)
+            .aliases(true)
+            .allow_source(true)
+            .allow_repeat(true)
+            .allow_sleep(true)
+            .allow_history(true)
+            .allow_clear(true)
+    }
+
+    // lines like `! ls -la` run through the shell, in batches too; without the space
+    // the `!` is left to history expansion
+    pub fn allow_shell_escape(mut self, on: bool) -> Self {
+        self.shell_escape = on;
+        self
+    }
+
+    pub fn undefined_variables(mut self, undefined: UndefinedVariable) -> Self {
+        self.undefined = undefined;
+        self
+    }
+
+    // a script run before the interactive loop starts, skipped if it does not exist
+    pub fn rc_file(mut self, path: impl Into<PathBuf>) -> Self {
+        self.rc = Some(path.into());
+        self
+    }
+
+    // `****` for the value of `task`'s arg `arg` in the history and the transcript, the
+    // task itself still gets it
+    pub fn mask_arg(mut self, task: impl Into<String>, arg: impl Into<String>) -> Self {
+        self.secrets
+            .entry(task.into())
+            .or_default()
+            .push(arg.into());
+        self
+    }
+
+    // every command run and every error shown, appended with the time
+    pub fn transcript(mut self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        self.transcript = Some(path.to_str().map_or(path.clone(), path::expand_home));
+        self
+    }
+
+    // keeps the prompt history between sessions
+    pub fn history_file(self, path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        self.history.borrow_mut().file =
+            Some(path.to_str().map_or(path.clone(), path::expand_home));
+        self
+    }
+
+    // the most entries kept, 1000 unless set, the oldest go first
+    pub fn history_limit(self, limit: usize) -> Self {
+        self.history.borrow_mut().limit = Some(limit);
+        self
+    }
+
+    pub fn history_dedup_consecutive(self, on: bool) -> Self {
+        self.history.borrow_mut().dedup_consecutive = on;
+        self
+    }
+
+    pub fn history_dedup_all(self, on: bool) -> Self {
+        self.history.borrow_mut().dedup_all = on;
+        self
+    }
+
+    // on by default
+    pub fn history_skip_blank(self, on: bool) -> Self {
+        self.history.borrow_mut().skip_blank = on;
+        self
+    }
+
+    // ` cmd` runs without being recorded
+    pub fn history_ignore_leading_space(self, on: bool) -> Self {
+        self.history.borrow_mut().ignore_leading_space = on;
+        self
+    }
+
+    // stop at the first error of the rc file instead of reporting it and going on
+    pub fn rc_strict(mut self, on: bool) -> Self {
+        self.rc_strict = on;
+        self
+    }
+
+    // what tasks poll for Ctrl+C, it is set while a command line runs at the prompt
+    pub fn cancel_token(&self) -> CancelToken {
+        self.cancel.clone()
+    }
+
+    pub fn set_variable(&self, name: &str, value: &str) {
+        self.vars
+            .borrow_mut()
+            .insert(name.to_string(), value.to_string());
+    }
+
+    // the value `$name` expands to, set variables shadow the environment
+    pub fn variable(&self, name: &str) -> Option<String> {
+        self.vars
+            .borrow()
+            .get(name)
+            .cloned()
+            .or_else(|| std::env::var(name).ok())
+    }
+
+    pub fn completion_options(mut self, options: CompleteOptions) -> Self {
+        self.matcher.options = options;
+        self
+    }
+
+    // panics when the name or an alias of the task is taken, see `try_add_task`
+    pub fn add_task<T: Task<C> + 'static>(self, t: T) -> Self {
+        match self.try_add_task(t) {
+            Ok(cli) => cli,
+            Err(e) => panic!("{}", e),
+        }
+    }
+
+    pub fn try_add_task<T: Task<C> + 'static>(self, t: T) -> Result<Self, CliError> {
+        // the name and aliases of a command, every one of them starts it
+        fn names(cmd: &Command) -> Vec<String> {
+            [cmd.get_name()]
+                .into_iter()
+                .chain(cmd.get_all_aliases())
+                .map(str::to_string)
+                .collect()
+        }
+        let cmd = t.command();
+        let taken = names(&cmd)
+            .into_iter()
+            .find(|n| n == "help" || self.find_task(n).is_some());
+        match taken {
+            Some(name) => Err(CliError::DuplicateCommand(name)),
+            None => Ok(self.replace_task(t)),
+        }
+    }
+
+    // a builtin like `set` or `history`, which panics like `add_task` on a name a task of
+    // the user's has; turning one on twice only registers it again
+    fn builtin<T: Task<C> + 'static>(mut self, t: T) -> Self {
+        let name = t.command().get_name().to_string();
+        self = match self.builtins.contains(&name) {
+            true => self.replace_task(t),
+            false => self.add_task(t),
+        };
+        self.builtins.insert(name);
+        self
+    }
+
+    // in place of any task of the same name
+    pub fn replace_task<T: Task<C> + 'static>(mut self, t: T) -> Self {
+        let cmd = t.command();
+        let name = cmd.get_name().to_string();
+        // the user's from now on, `source` and `repeat` included: the cli only runs those
+        // itself while they are in `builtins`
+        self.builtins.remove(&name);
+        // the aliases of a task replaced go with it
+        self.canonical.retain(|_, n| *n != name);
+        for alias in cmd.get_all_aliases() {
+            self.canonical.insert(alias.to_string(), name.clone());
+        }
+        if self.cmds.insert(name.clone(), Box::new(t)).is_none() {
+            self.order.push(name);
+        }
+        self.names = OnceCell::new();
+        self.root = OnceCell::new();
+        self
+    }
+
+    // a small command without a type of its own, e.g.
+    // `add_fn_task(Command::new("ping"), |_| TaskAction::Continue)`
+    pub fn add_fn_task<F: Fn(&ArgMatches) -> TaskAction + 'static>(
+        self,
+        cmd: Command,
+        f: F,
+    ) -> Self {
+        self.add_task(fn_task::FnTask { cmd, f })
+    }
+
+    pub fn add_raw_task<T: RawTask + 'static>(mut self, t: T) -> Self {
+        let t: Rc<dyn RawTask> = Rc::new(t);
+        self.raw.insert(t.name().to_string(), t.clone());
+        self.add_task(raw::Raw(t))
+    }
+
+    // a task that can also be put in the background with `cmd &`, which brings
+    // the `jobs` and `wait` builtins along
+    pub fn add_task_send<T: Task + Send + Sync + 'static>(mut self, t: T) -> Self {
+        if self.send.is_empty() {
+            let jobs = self.jobs.clone();
+            self = self
+                .builtin(builtins::Jobs(jobs.clone()))
+                .builtin(builtins::Wait(jobs));
+        }
+        let t: Arc<dyn Task + Send + Sync> = Arc::new(t);
+        self.send
+            .insert(t.command().get_name().to_string(), t.clone());
+        self.add_task(jobs::Shared(t))
+    }
+
+    fn tasks(&self) -> impl Iterator<Item = &dyn Task<C>> {
+        self.order.iter().map(|n| self.cmds[n].as_ref())
+    }
+
+    // the words of a line, once variables are expanded
+    fn split(&self, line: &str) -> Result<Vec<String>, CliError> {
+        let expanded;
+        let line = if self.expand_vars {
+            expanded = vars::expand(line, |n| self.variable(n), self.undefined)?;
+            &expanded
+        } else {
+            line
+        };
+        shlex::split(line).ok_or_else(|| CliError::Lex("Invalid quoting".into()))
+    }
+
+    // the words up to `--` are split as usual, the rest is also kept as typed; clap
+    // still sees the words after `--`, as far as they can be split at all
+    fn split_raw<'a>(&self, line: &'a str) -> Result<(Vec<String>, Option<&'a str>), CliError> {
+        let (line, raw) = lex::split_raw(line);
+        let mut args = self.split(line)?;
+        if let Some(raw) = raw {
+            args.push("--".into());
+            args.extend(shlex::split(raw).unwrap_or_default());
+        }
+        Ok((args, raw))
+    }
+
+    pub fn parse(&self, line: &str) -> Result<Option<ArgMatches>, CliError> {
+        self.parse_args(self.split_raw(line)?.0)
+    }
+
+    // a line for one known command, split like any other line but parsed by `T`
+    pub fn parse_into<T: clap::Parser>(&self, line: &str) -> Result<T, CliError> {
+        T::try_parse_from(self.split(line)?).map_err(CliError::Clap)
+    }
+
+    // for lines that come already split, they are taken as they are
+    pub fn parse_args<I: IntoIterator<Item = S>, S: Into<String>>(
+        &self,
+        args: I,
+    ) -> Result<Option<ArgMatches>, CliError> {
+        let mut args = args.into_iter().map(Into::into).collect::<Vec<String>>();
+        if args.is_empty() {
+            return Ok(None);
+        }
+        if let Some(name) = self.expand_abbreviation(&args[0])? {
+            args[0] = name;
+        }
+        let cmd = self.command();
+        cmd.try_get_matches_from(&args).map(Some).map_err(|e| {
+            use clap::error::{ContextKind, ContextValue};
+            let unknown = e
+                .get(ContextKind::InvalidSubcommand)
+                .or_else(|| e.get(ContextKind::InvalidArg));
+            match unknown {
+                // only the task name itself, nested subcommands keep clap's message
+                Some(ContextValue::String(s)) if *s == args[0] => self.unknown_command(s),
+                _ => CliError::Clap(e),
+            }
+        })
+    }
+
+    // the one task an unknown first token is a prefix of, aliases count towards their task
+    fn expand_abbreviation(&self, token: &str) -> Result<Option<String>, CliError> {
+        let names = self.names();
+        if !self.abbreviations || names.iter().any(|n| n == token) {
+            return Ok(None);
+        }
+        let mut matches = names
+            .iter()
+            .filter(|n| n.starts_with(token))
+            .map(|n| {
+                self.find_task(n)
+                    .map_or(n.to_string(), |t| t.command().get_name().to_string())
+            })
+            .collect::<Vec<_>>();
+        matches.sort();
+        matches.dedup();
+        match matches.len() {
+            0 => Ok(None),
+            1 => Ok(matches.pop()),
+            _ => Err(CliError::AmbiguousCommand {
+                name: token.to_string(),
+                candidates: matches,
+            }),
+        }
+    }
+
+    fn unknown_command(&self, name: &str) -> CliError {
+        // with a fallback the name may well be right
+        if self.fallback.is_some() {
+            return CliError::UnknownCommand {
+                name: name.to_string(),
+                suggestions: vec![],
+            };
+        }
+        let names = self.names();
+        CliError::UnknownCommand {
+            name: name.to_string(),
+            suggestions: distance::closest(name, &names, 3)
+                .into_iter()
+                .map(|n| n.to_string())
+                .collect(),
+        }
+    }
+
+    // the count and command of `repeat N COMMAND...`, anything else is left for clap to
+    // complain about
+    fn repeat_command<'a>(&self, line: &'a str) -> Option<(usize, &'a str)> {
+        let rest = line.trim_start().strip_prefix("repeat")?;
+        if !self.repeats() || !rest.starts_with(char::is_whitespace) {
+            return None;
+        }
+        let (count, cmd) = rest.trim_start().split_once(char::is_whitespace)?;
+        let cmd = cmd.trim_start();
+        (!cmd.is_empty()).then_some((count.parse().ok()?, cmd))
+    }
+
+    // only while `repeat` is the builtin, a task of the user's by that name runs as any
+    // other
+    fn repeats(&self) -> bool {
+        self.builtins.contains("repeat")
+    }
+
+    // `source` and `repeat` while they are the builtins; a `repeat` that did not come in
+    // as a line, e.g. from `run_args` or with `$N` as its count, still runs its command
+    fn own(&self, name: &str, matches: &ArgMatches) -> Option<Own> {
+        match name {
+            "source" if self.builtins.contains(name) => Some(Own::Source),
+            "repeat" if self.repeats() => {
+                let count = *matches.get_one::<usize>("count").unwrap();
+                let cmd = matches
+                    .get_many::<String>("command")
+                    .unwrap()
+                    .map(|w| quote(w.clone()))
+                    .collect::<Vec<_>>()
+                    .join(" ");
+                Some(Own::Repeat(count, cmd))
+            }
+            _ => None,
+        }
+    }
+
+    fn repeat(&self, ctx: &C, count: usize, cmd: &str) -> Result<TaskAction, CliError> {
+        for i in 0..count {
+            if let Some(action) = self.repeat_stops(i, count, self.run(ctx, cmd)) {
+                return Ok(action);
+            }
+        }
+        Ok(TaskAction::Continue)
+    }
+
+    // what the `i`th run of a repeated command ends the repeat with, if it does
+    fn repeat_stops(
+        &self,
+        i: usize,
+        count: usize,
+        r: Result<TaskAction, CliError>,
+    ) -> Option<TaskAction> {
+        let stopped = |e: &CliError| {
+            self.print_error(e);
+            TaskAction::Error(format!("repeat stopped after {} of {} runs", i, count))
+        };
+        match r {
+            Ok(TaskAction::Continue) => None,
+            Ok(TaskAction::Error(e)) => Some(stopped(&CliError::Task(e))),
+            Ok(action) => Some(action),
+            Err(e) => Some(stopped(&e)),
+        }
+    }
+
+    // completing `repeat N COMMAND...` is completing its command
+    fn repeated<'a>(&self, name: &str, rest: &'a [&'a str]) -> Option<(&'a str, &'a [&'a str])> {
+        match rest {
+            [count, cmd @ ..] if name == "repeat" && self.repeats() && !cmd.is_empty() => {
+                Some((count, cmd))
+            }
+            _ => None,
+        }
+    }
+
+    // the after hooks stay with the foreground, a job's action is only reported
+    fn run_background(&self, cmd: &str) -> Result<TaskAction, CliError> {
+        let (args, raw) = self.split_raw(cmd)?;
+        let matches = self
+            .parse_args(args)?
+            .ok_or_else(|| CliError::Lex("expected a command before &".into()))?;
+        let (name, matches) = matches.subcommand().unwrap();
+        let task = match self.send.get(name) {
+            Some(t) => t.clone(),
+            None => {
+                return Ok(TaskAction::Error(format!(
+                    "'{}' cannot run in the background",
+                    name
+                )))
+            }
+        };
+        if let Err(e) = self.before_hooks(name, matches) {
+            return Ok(TaskAction::Error(e));
+        }
+        let (matches, raw) = (matches.clone(), raw.map(str::to_string));
+        let id = self
+            .jobs
+            .borrow_mut()
+            .spawn(cmd, move || task.action_raw(&(), &matches, raw.as_deref()));
+        println!("[{}] {}", id, cmd);
+        Ok(TaskAction::Continue)
+    }
+
+    fn starts_with_task(&self, line: &str) -> bool {
+        let word = line.split_whitespace().next().unwrap_or_default();
+        word == "help"
+            || self.find_task(word).is_some()
+            || !matches!(self.expand_abbreviation(word), Ok(None))
+    }
+
+    // what is left to type of the usage of the task the line starts with, once it ends
+    // with a space
+    fn usage_hint(&self, line: &str) -> Option<String> {
+        if !line.ends_with(char::is_whitespace) || self.shell_command(line).is_some() {
+            return None;
+        }
+        let words = shlex::split(line)?;
+        let (name, args) = words.split_first()?;
+        let mut cmd = self.find_task(name)?.command();
+        cmd.build();
+        missing_usage(&cmd, &args.iter().map(|a| a.as_str()).collect::<Vec<_>>())
+    }
+
+    // what the prompt colors the first word by
+    fn command_known(&self, line: &str) -> Option<bool> {
+        let word = line.split_whitespace().next()?;
+        if self.shell_command(line).is_some() {
+            return None;
+        }
+        Some(self.starts_with_task(line) || self.aliases.borrow().contains_key(word))
+    }
+
+    fn shell_command<'a>(&self, line: &'a str) -> Option<&'a str> {
+        self.shell_escape.then(|| shell::command(line)).flatten()
+    }
+
+    // the raw task a line starts with, and the rest of the line
+    fn raw_task<'a>(&self, line: &'a str) -> Option<(&str, &dyn RawTask, &'a str)> {
+        let line = line.trim_start();
+        let (name, rest) = line.split_once(char::is_whitespace).unwrap_or((line, ""));
+        self.raw
+            .get_key_value(name)
+            .map(|(name, t)| (name.as_str(), t.as_ref(), rest.trim_start()))
+    }
+
+    // `ctx` is handed to the action of every task the line runs
+    pub fn run(&self, ctx: &C, line: &str) -> Result<TaskAction, CliError> {
+        if let Some(cmd) = self.shell_command(line) {
+            return shell::run(cmd);
+        }
+        let expanded = &alias::expand(line, &self.aliases.borrow())?;
+        match self.resolve(line, expanded) {
+            Resolved::Done(r) => r,
+            Resolved::Repeat(count, cmd) => self.repeat(ctx, count, cmd),
+            Resolved::Args(args, raw) => self.or_fallback(line, self.run_with_raw(ctx, args, raw)),
+        }
+    }
+
+    // all of `run` short of parsing and running a task, which the async path does its own
+    // way; `expanded` is `line` with its aliases expanded
+    fn resolve<'a>(&self, line: &str, expanded: &'a str) -> Resolved<'a> {
+        // the rest of the line is the raw task's, a `&` included
+        if let Some((name, t, rest)) = self.raw_task(expanded) {
+            // raw tasks have no matches to show the hooks
+            let action = self.dispatch(name, &ArgMatches::default(), || t.action(rest));
+            return Resolved::Done(Ok(action));
+        }
+        if let Some(cmd) = lex::background(expanded) {
+            return Resolved::Done(self.run_background(cmd.trim()));
+        }
+        if let Some((count, cmd)) = self.repeat_command(expanded) {
+            return Resolved::Repeat(count, cmd);
+        }
+        match (self.split_raw(expanded), &self.fallback) {
+            (Ok((args, raw)), _) => Resolved::Args(args, raw),
+            // what the fallback gets need not split at all
+            (Err(CliError::Lex(_)), Some(f)) if !self.starts_with_task(expanded) => {
+                Resolved::Done(Ok(f(line).unwrap_or_else(TaskAction::Error)))
+            }
+            (Err(e), _) => Resolved::Done(Err(e)),
+        }
+    }
+
+    // an unknown command is the fallback's, if there is one
+    fn or_fallback(
+        &self,
+        line: &str,
+        r: Result<TaskAction, CliError>,
+    ) -> Result<TaskAction, CliError> {
+        match (r, &self.fallback) {
+            (Err(CliError::UnknownCommand { .. }), Some(f)) => {
+                Ok(f(line).unwrap_or_else(TaskAction::Error))
+            }
+            (r, _) => r,
+        }
+    }
+
+    pub fn run_args<I: IntoIterator<Item = S>, S: Into<String>>(
+        &self,
+        ctx: &C,
+        args: I,
+    ) -> Result<TaskAction, CliError> {
+        self.run_with_raw(ctx, args, None)
+    }
+
+    fn run_with_raw<I: IntoIterator<Item = S>, S: Into<String>>(
+        &self,
+        ctx: &C,
+        args: I,
+        raw: Option<&str>,
+    ) -> Result<TaskAction, CliError> {
+        match self.parse_or_help(args)? {
+            Some(matches) => Ok(self.action_raw(ctx, &matches, raw)),
+            None => Ok(TaskAction::Continue),
+        }
+    }
+
+    // `None` for nothing to run, which the help that was asked for and printed counts as
+    fn parse_or_help<I: IntoIterator<Item = S>, S: Into<String>>(
+        &self,
+        args: I,
+    ) -> Result<Option<ArgMatches>, CliError> {
+        use clap::error::ErrorKind;
+        match self.parse_args(args) {
+            // asking for help is not a failure
+            Err(CliError::Clap(e))
+                if matches!(e.kind(), ErrorKind::DisplayHelp | ErrorKind::DisplayVersion) =>
+            {
+                // styled like clap would print it, plain into a pipe
+                let help = e.render();
+                match std::io::stdout().is_terminal() {
+                    true => pager::page(&help.ansi().to_string())?,
+                    false => pager::page(&help.to_string())?,
+                }
+                Ok(None)
+            }
+            r => r,
+        }
+    }
+
+    // `a && b || c` runs left to right, each operator deciding on whether the
+    // command before it succeeded; from here on a failed task is an error
+    fn run_chain(&self, ctx: &C, line: &str) -> Result<TaskAction, CliError> {
+        self.record_command(line);
+        let chain = self.split_chain(line)?;
+        let mut last = Ok(TaskAction::Continue);
+        for (op, cmd) in chain {
+            if self.chain_skips(op, &last) {
+                continue;
+            }
+            last = self.run(ctx, cmd);
+            if self.chain_ends(&mut last) {
+                break;
+            }
+        }
+        last
+    }
+
+    // whether the operator before a command of a chain passes it over, given how the
+    // command before it went
+    fn chain_skips(&self, op: Option<lex::Chain>, last: &Result<TaskAction, CliError>) -> bool {
+        match (op, last) {
+            (Some(lex::Chain::And), Err(_)) | (Some(lex::Chain::Or), Ok(_)) => true,
+            // the failure `||` recovers from is still worth seeing
+            (Some(lex::Chain::Or), Err(e)) => {
+                self.print_error(e);
+                false
+            }
+            _ => false,
+        }
+    }
+
+    // a failed task made an error for the next operator, and whether the chain is over
+    fn chain_ends(&self, last: &mut Result<TaskAction, CliError>) -> bool {
+        if let Ok(TaskAction::Error(message)) = last {
+            *last = Err(CliError::Task(std::mem::take(message)));
+        }
+        matches!(last, Ok(ref a) if *a != TaskAction::Continue) || self.cancel.is_cancelled()
+    }
+
+    fn split_chain<'a>(
+        &self,
+        line: &'a str,
+    ) -> Result<Vec<(Option<lex::Chain>, &'a str)>, CliError> {
+        lex::split_chain(line, |c| self.starts_raw(c))
+            .ok_or_else(|| CliError::Lex("expected a command around && or ||".into()))
+    }
+
+    // a command of a raw task, which takes the rest of the line with it however it goes on
+    fn starts_raw(&self, cmd: &str) -> bool {
+        alias::expand(cmd, &self.aliases.borrow()).is_ok_and(|c| self.raw_task(&c).is_some())
+    }
+
+    // a chain with a raw task in it, e.g. `a && calc 1`, whose `;` are not the line's
+    fn claims(&self, chain: &str) -> bool {
+        lex::split_chain(chain, |c| self.starts_raw(c))
+            .and_then(|chain| chain.last().map(|&(_, c)| self.starts_raw(c)))
+            .unwrap_or(false)
+    }
+
+    // the commands of a script or of several lines typed in one go
+    fn commands<'a, I: Iterator<Item = Result<S, CliError>> + 'a, S: AsRef<str> + 'a>(
+        &'a self,
+        lines: I,
+        quotes: bool,
+    ) -> impl Iterator<Item = Result<(usize, String), CliError>> + 'a {
+        script::commands(lines, quotes, |c| self.claims(c))
+    }
+
+    // parses like `run` but stops short of the action
+    fn check(&self, line: &str) -> Result<(), CliError> {
+        if self.shell_command(line).is_some() {
+            return Ok(());
+        }
+        let line = &alias::expand(line, &self.aliases.borrow())?;
+        if let Some(cmd) = lex::background(line) {
+            let name = match self.parse(cmd)? {
+                Some(m) => m.subcommand_name().unwrap().to_string(),
+                None => return Err(CliError::Lex("expected a command before &".into())),
+            };
+            return match self.send.contains_key(&name) {
+                true => Ok(()),
+                false => Err(CliError::Task(format!(
+                    "'{}' cannot run in the background",
+                    name
+                ))),
+            };
+        }
+        if self.raw_task(line).is_some() {
+            return Ok(());
+        }
+        if let Some((_, cmd)) = self.repeat_command(line) {
+            return self.check(cmd);
+        }
+        match self.parse(line) {
+            Err(CliError::Clap(e))
+                if matches!(
+                    e.kind(),
+                    clap::error::ErrorKind::DisplayHelp | clap::error::ErrorKind::DisplayVersion
+                ) =>
+            {
+                Ok(())
+            }
+            Err(CliError::UnknownCommand { .. }) if self.fallback.is_some() => Ok(()),
+            r => r.map(|_| ()),
+        }
+    }
+
+    // a line typed at the prompt, which may hold several commands
+    fn run_line(&self, ctx: &C, line: &str) -> Result<TaskAction, CliError> {
+        let mut commands = lex::split_unquoted(line, ';', |c| self.claims(c))
+            .into_iter()
+            .peekable();
+        while let Some(cmd) = commands.next() {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let r = self.run_chain(ctx, cmd);
+            if let Some(r) = self.line_ends(r, commands.peek().is_some()) {
+                return r;
+            }
+        }
+        Ok(TaskAction::Continue)
+    }
+
+    // what a command of a line ends the line with; the line goes on after an error when
+    // there are `more` commands
+    fn line_ends(
+        &self,
+        r: Result<TaskAction, CliError>,
+        more: bool,
+    ) -> Option<Result<TaskAction, CliError>> {
+        match r {
+            Ok(TaskAction::Continue) => None,
+            Err(e) if more => {
+                self.print_error(&e);
+                None
+            }
+            r => Some(r),
+        }
+    }
+
+    fn before_hooks(&self, name: &str, matches: &ArgMatches) -> Result<(), String> {
+        self.before
+            .iter()
+            .map(|h| h(name, matches))
+            .find(Result::is_err)
+            .unwrap_or(Ok(()))
+    }
+
+    // a task's action wrapped in the hooks
+    fn dispatch<F: FnOnce() -> TaskAction>(
+        &self,
+        name: &str,
+        matches: &ArgMatches,
+        f: F,
+    ) -> TaskAction {
+        match self.start_task(name, matches) {
+            Ok(started) => {
+                let action = f();
+                self.end_task(name, started, action)
+            }
+            Err(action) => action,
+        }
+    }
+
+    // the before hooks, `Err` with what a hook that refused the task said
+    fn start_task(&self, name: &str, matches: &ArgMatches) -> Result<Started, TaskAction> {
+        self.before_hooks(name, matches)
+            .map_err(TaskAction::Error)?;
+        Ok(Started {
+            _themed: input::Themed::start(self.theme),
+            at: Instant::now(),
+        })
+    }
+
+    fn end_task(&self, name: &str, started: Started, action: TaskAction) -> TaskAction {
+        let elapsed = started.at.elapsed();
+        drop(started);
+        for h in &self.after {
+            h(name, &action, elapsed);
+        }
+        action
+    }
+
+    // the task registered under `name` or one of its aliases
+    fn find_task(&self, name: &str) -> Option<&dyn Task<C>> {
+        self.cmds
+            .get(name)
+            .or_else(|| self.cmds.get(self.canonical.get(name)?))
+            .map(|t| t.as_ref())
+    }
+
+    // completion of a task's args, only when it lands on a value the Cli provides
+    fn dynamic_completion(
+        &self,
+        t: &dyn Task<C>,
+        args: &[&str],
+    ) -> Option<(Vec<String>, Vec<String>)> {
+        let cmd = t.command();
+        let used = Cell::new(false);
+        let values = |a: &Arg, token: &str| {
+            let hint = a.get_value_hint();
+            let r = match self
+                .completers
+                .get(&(cmd.get_name().to_string(), a.get_id().to_string()))
+                .or_else(|| self.hint_sources.get(&hint))
+            {
+                Some(f) => f(token),
+                None if hint == ValueHint::CommandName => self.names(),
+                None => return None,
+            };
+            used.set(true);
+            Some(r)
+        };
+        args_completion(&cmd, args, &values).filter(|_| used.get())
+    }
+
+    fn variable_completion(&self, args: &[&str]) -> Option<(Vec<String>, Vec<String>)> {
+        let (last, head) = args.split_last()?;
+        let mut names = self.vars.borrow().keys().cloned().collect::<Vec<_>>();
+        names.extend((self.variable_names)(last));
+        names.sort();
+        names.dedup();
+        let candidates = vars::variable_candidates(last, names)?;
+        Some((
+            head.iter().map(|s| s.to_string()).collect(),
+            self.matcher.rank(candidates, last),
+        ))
+    }
+
+    // first-token completion candidates
+    // what may start a command: the tasks and the aliases of the moment
+    fn first_words(&self) -> Vec<String> {
+        let mut aliases = self.aliases.borrow().keys().cloned().collect::<Vec<_>>();
+        aliases.sort();
+        self.names().into_iter().chain(aliases).collect()
+    }
+
+    fn names(&self) -> Vec<String> {
+        self.names
+            .get_or_init(|| {
+                self.tasks()
+                    .flat_map(|t| {
+                        let c = t.command();
+                        [c.get_name().to_string()]
+                            .into_iter()
+                            .chain(c.get_all_aliases().map(|a| a.to_string()))
+                            .collect::<Vec<_>>()
+                    })
+                    .chain(["help".to_string()])
+                    .collect()
+            })
+            .clone()
+    }
+
+    // the partial command name after the builtin `help`, which takes just one
+    fn help_topic<'a>(&self, name: &str, rest: &[&'a str]) -> Option<&'a str> {
+        match rest {
+            [topic] if name == "help" && self.find_task(name).is_none() => Some(topic),
+            _ => None,
+        }
+    }
+
+    fn complete_line(&self, cycle: &mut Cycle, line: &str, forward: bool) -> Option<String> {
+        if cycle.is_active(line) {
+            return cycle.step(forward).map(|r| r.to_string());
+        }
+        // walking backwards only makes sense within a session
+        if !forward {
+            return None;
+        }
+        let tokens = lex::split_partial(line);
+        let tokens = tokens.iter().map(|s| s.as_str()).collect::<Vec<_>>();
+        let completions = self.completions(&tokens)?;
+        let r = completions.replacement.to_string();
+        if lex::split_partial(&r) != tokens {
+            cycle.reset();
+            return Some(r);
+        }
+        if completions.candidates.len() < 2 {
+            return None;
+        }
+        let candidates = completions
+            .candidates
+            .iter()
+            .map(|c| c.text.to_string())
+            .collect();
+        cycle.start(line, candidates).map(|r| r.to_string())
+    }
+
+    // completes the part of the line before the cursor and leaves the rest alone,
+    // giving back the new line and where the cursor ends up
+    fn complete_at(
+        &self,
+        cycle: &mut Cycle,
+        line: &str,
+        cursor: usize,
+        forward: bool,
+    ) -> Option<(String, usize)> {
+        let (head, tail) = split_at_cursor(line, cursor);
+        let r = self.complete_line(cycle, head, forward)?;
+        let cursor = r.chars().count();
+        Some((r + tail, cursor))
+    }
+
+    fn completion_key(
+        cli: &Rc<Self>,
+        cycle: &Rc<RefCell<Cycle>>,
+        listed: &Rc<Cell<bool>>,
+        forward: bool,
+    ) -> Box<EventHandleFn<State>> {
+        let (cli, cycle, listed) = (cli.clone(), cycle.clone(), listed.clone());
+        Box::new(move |_, _, out: &mut std::io::Stdout, state: &mut State| {
+            let line = state.0.editor.data.to_string();
+            let mut cycle = cycle.borrow_mut();
+            let cursor = state.0.editor.position();
+            match cli.complete_at(&mut cycle, &line, cursor, forward) {
+                Some((r, cursor)) => {
+                    state.0.editor.replace(&Graphemes::from(r));
+                    state.0.editor.position.set(cursor);
+                    // draw now, the listing below must not be wiped by the next render
+                    state.0.next = state.0.editor.clone();
+                    state.render(out)?;
+                    state.0.prev = state.0.editor.clone();
+                }
+                None => {
+                    if cli.no_match.give(out, state)? {
+                        listed.set(true);
+                    }
+                }
+            }
+            let line = state.0.editor.data.to_string();
+            if cycle.is_active(split_at_cursor(&line, state.0.editor.position()).0) {
+                listing::show(
+                    out,
+                    &cycle.listing(),
+                    cli.completion_rows,
+                    cli.theme.suggestion,
+                )?;
+                listed.set(true);
+            }
+            Ok(false)
+        })
+    }
+
+    pub fn prompt(self: &Rc<Self>) -> readline::Builder {
+        let mut b = KeyBind::default();
+        let cli = self.clone();
+        let cycle = Rc::new(RefCell::new(Cycle::default()));
+        let listed = Rc::new(Cell::new(false));
+        let esc = cycle.clone();
+        b.assign(vec![
+            (
+                Event::Key(KeyEvent {
+                    code: KeyCode::Tab,
+                    modifiers: KeyModifiers::NONE,
+                }),
+                Self::completion_key(&cli, &cycle, &listed, true),
+            ),
+            // `keys::normalize` hands Shift+Tab over as BackTab, whatever it came with
+            (
+                Event::Key(KeyEvent {
+                    code: KeyCode::BackTab,
+                    modifiers: KeyModifiers::NONE,
+                }),
+                Self::completion_key(&cli, &cycle, &listed, false),
+            ),
+            (
+                Event::Key(KeyEvent {
+                    code: KeyCode::Esc,
+                    modifiers: KeyModifiers::NONE,
+                }),
+                Box::new(move |_, _, _: &mut std::io::Stdout, state: &mut State| {
+                    let line = state.0.editor.data.to_string();
+                    let (head, tail) = split_at_cursor(&line, state.0.editor.position());
+                    if let Some(r) = esc.borrow_mut().cancel(head) {
+                        let cursor = r.chars().count();
+                        state.0.editor.replace(&Graphemes::from(r + tail));
+                        state.0.editor.position.set(cursor);
+                    }
+                    Ok(false)
+                }) as Box<EventHandleFn<State>>,
+            ),
+            (
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('d'),
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                keys::delete_or_eof(),
+            ),
+            (
+                Event::Key(KeyEvent {
+                    code: KeyCode::Char('l'),
+                    modifiers: KeyModifiers::CONTROL,
+                }),
+                // the line is drawn again as it was on the next round
+                handler::reload::<Buffer, readline::state::With, std::io::Stdout>(),
+            ),
+        ]);
+        b.assign(edit::edit_keys(&self.word_chars));
+        // over the crate's own bindings
+        for (keys, f) in self.bindings.iter().filter(|(k, _)| k.0.len() == 1) {
+            let f = f.clone();
+            b.assign(vec![(
+                keys::normalize(Event::Key(keys.0[0])),
+                Box::new(
+                    move |size, ch, out: &mut std::io::Stdout, state: &mut State| {
+                        f(size, ch, out, state)
+                    },
+                ) as Box<EventHandleFn<State>>,
+            )]);
+        }
+
+        // the line in $VISUAL or $EDITOR, after any chord of the same keys bound above
+        let (ctrl_x, ctrl_e) = (
+            edit::key(KeyCode::Char('x'), KeyModifiers::CONTROL),
+            edit::key(KeyCode::Char('e'), KeyModifiers::CONTROL),
+        );
+        let (lines, shown) = (self.edited_lines, listed.clone());
+        let visual: Rc<EventHandleFn<State>> =
+            Rc::new(move |_, _, out: &mut std::io::Stdout, state: &mut State| {
+                editor::edit_line(out, state, lines, &shown)
+            });
+
+        let mut builder = readline::Builder::default()
+            .label(self.label())
+            .label_color(self.theme.label);
+        if let Some(limit) = self.history.borrow().limit {
+            builder = builder.limit_history_size(limit);
+        }
+        builder.handler(Keys {
+            bind: b,
+            listed,
+            cycle,
+            hints: self.history_hints,
+            theme: self.theme,
+            hint: None,
+            search: None,
+            highlight: self.highlight.then(|| {
+                let cli = self.clone();
+                Box::new(move |line: &str| cli.command_known(line)) as keys::Highlight
+            }),
+            usage: self.usage_hints.then(|| {
+                let cli = self.clone();
+                Box::new(move |line: &str| cli.usage_hint(line)) as keys::Usage
+            }),
+            usage_shown: false,
+            accept: self.search_accept,
+            user: self
+                .bindings
+                .iter()
+                .filter(|(k, _)| k.0.len() == 1)
+                .map(|(k, _)| keys::normalize(Event::Key(k.0[0])))
+                .collect(),
+            chords: self
+                .bindings
+                .iter()
+                .filter(|(k, _)| k.0.len() > 1)
+                .map(|(k, f)| {
+                    (
+                        k.0.iter()
+                            .map(|k| keys::normalize(Event::Key(*k)))
+                            .collect(),
+                        f.clone(),
+                    )
+                })
+                .chain(Some((vec![ctrl_x, ctrl_e], visual)))
+                .collect(),
+            chord_timeout: self.chord_timeout,
+            vi: (self.edit_mode == EditMode::Vi).then(|| vi::Vi::new(&self.word_chars)),
+            hold_pastes: self.hold_pastes,
+            ctrl_c: self.ctrl_c,
+            double_ctrl_c: self.double_ctrl_c,
+            last_ctrl_c: None,
+            burst: false,
+            rprompt: self.rprompt.clone(),
+            printer: self.printer.clone(),
+        })
+    }
+
+    // runs until a task asks to leave, which is handed back like from the interactive loop
+    pub fn run_batch(&self, ctx: &C, cmd: &str) -> Result<TaskAction, CliError> {
+        self.run_batch_with(ctx, cmd, BatchOptions::default())
+            .map(|r| r.action)
+    }
+
+    pub fn run_batch_with(
+        &self,
+        ctx: &C,
+        cmd: &str,
+        options: BatchOptions,
+    ) -> Result<BatchReport, CliError> {
+        self.run_lines(ctx, cmd.split('\n').map(Ok), options)
+    }
+
+    // streams the script, it never has to be read as a whole
+    pub fn run_batch_from_reader<R: BufRead>(&self, ctx: &C, r: R) -> Result<TaskAction, CliError> {
+        self.run_lines(
+            ctx,
+            r.lines().map(|l| l.map_err(CliError::Io)),
+            BatchOptions::default(),
+        )
+        .map(|r| r.action)
+    }
+
+    pub fn run_script<P: AsRef<Path>>(&self, ctx: &C, path: P) -> Result<TaskAction, CliError> {
+        let path = path.as_ref();
+        let file = |source| CliError::File {
+            path: path.to_path_buf(),
+            source,
+        };
+        let f = File::open(path).map_err(file)?;
+        self.run_file(ctx, path, f, BatchOptions::default())
+            .map(|r| r.action)
+    }
+
+    fn run_file(
+        &self,
+        ctx: &C,
+        path: &Path,
+        f: File,
+        options: BatchOptions,
+    ) -> Result<BatchReport, CliError> {
+        self.run_lines(ctx, Self::file_lines(path, f), options)
+    }
+
+    fn file_lines(path: &Path, f: File) -> impl Iterator<Item = Result<String, CliError>> + '_ {
+        let file = |source| CliError::File {
+            path: path.to_path_buf(),
+            source,
+        };
+        BufReader::new(f).lines().map(move |l| l.map_err(file))
+    }
+
+    fn run_rc(&self, ctx: &C) -> Result<TaskAction, CliError> {
+        match self.open_rc()? {
+            Some((path, f, options)) => self.run_file(ctx, &path, f, options).map(|r| r.action),
+            None => Ok(TaskAction::Continue),
+        }
+    }
+
+    // the rc file to run and how, `None` without one
+    fn open_rc(&self) -> Result<Option<(PathBuf, File, BatchOptions)>, CliError> {
+        let path = match &self.rc {
+            Some(p) => p.to_str().map_or(p.clone(), path::expand_home),
+            None => return Ok(None),
+        };
+        let f = match File::open(&path) {
+            Ok(f) => f,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => return Ok(None),
+            Err(source) => return Err(CliError::File { path, source }),
+        };
+        let on_error = match self.rc_strict {
+            true => ErrorPolicy::Abort,
+            false => ErrorPolicy::Continue,
+        };
+        Ok(Some((path, f, BatchOptions::default().on_error(on_error))))
+    }
+
+    // every problem of a script, found without running any of it
+    pub fn validate_batch(&self, script: &str) -> Result<(), Vec<(usize, String)>> {
+        let mut problems = vec![];
+        for command in self.commands(script.split('\n').map(Ok), false) {
+            match command {
+                Ok((line, text)) => {
+                    let checked = self
+                        .split_chain(&text)
+                        .and_then(|chain| chain.iter().try_for_each(|(_, cmd)| self.check(cmd)));
+                    if let Err(e) = checked {
+                        problems.push((line, e.to_string()));
+                    }
+                }
+                Err(CliError::Script { line, message }) => problems.push((line, message)),
+                Err(e) => problems.push((0, e.to_string())),
+            }
+        }
+        match problems.is_empty() {
+            true => Ok(()),
+            false => Err(problems),
+        }
+    }
+
+    fn source(&self, ctx: &C, matches: &ArgMatches) -> TaskAction {
+        let (path, f, options) = match self.source_file(matches) {
+            Ok(file) => file,
+            Err(action) => return action,
+        };
+        self.source_depth.set(self.source_depth.get() + 1);
+        let r = self.run_file(ctx, path, f, options);
+        self.source_depth.set(self.source_depth.get() - 1);
+        Self::sourced(path, r)
+    }
+
+    // the script `source` runs and how, or why it cannot
+    fn source_file<'a>(
+        &self,
+        matches: &'a ArgMatches,
+    ) -> Result<(&'a PathBuf, File, BatchOptions), TaskAction> {
+        const MAX_DEPTH: usize = 16;
+        let path = matches.get_one::<PathBuf>("path").unwrap();
+        if self.source_depth.get() >= MAX_DEPTH {
+            return Err(TaskAction::Error(format!(
+                "source nested more than {} deep",
+                MAX_DEPTH
+            )));
+        }
+        let on_error = match matches.get_flag("ignore-errors") {
+            true => ErrorPolicy::Continue,
+            false => ErrorPolicy::Abort,
+        };
+        let f = File::open(path)
+            .map_err(|e| TaskAction::Error(format!("{}: {}", path.display(), e)))?;
+        Ok((path, f, BatchOptions::default().on_error(on_error)))
+    }
+
+    fn sourced(path: &Path, r: Result<BatchReport, CliError>) -> TaskAction {
+        match r {
+            Ok(r) => r.action,
+            Err(CliError::Batch { line, text, source }) => {
+                TaskAction::Error(format!("{}:{}: {}\n{}", path.display(), line, text, source))
+            }
+            Err(e) => TaskAction::Error(format!("{}: {}", path.display(), e)),
+        }
+    }
+
+    fn run_lines<I: Iterator<Item = Result<S, CliError>>, S: AsRef<str>>(
+        &self,
+        ctx: &C,
+        lines: I,
+        options: BatchOptions,
+    ) -> Result<BatchReport, CliError> {
+        let _unattended = input::Unattended::start(self.auto_confirm);
+        let _watch = self.cancel.watch();
+        let mut report = BatchReport::default();
+        for command in self.commands(lines, options.join_quotes) {
+            if self.cancel.is_cancelled() {
+                break;
+            }
+            let over = match self.next_command(command, &options)? {
+                Next::Run(line, text) => {
+                    let start = Instant::now();
+                    let r = self.run_chain(ctx, &text);
+                    self.batch_ran(&mut report, &options, line, text, r, Some(start))
+                }
+                Next::Broken(line, e) => {
+                    self.batch_ran(&mut report, &options, line, String::new(), Err(e), None)
+                }
+            };
+            if let ControlFlow::Break(r) = over {
+                r?;
+                break;
+            }
+        }
+        Ok(self.batch_done(report, &options))
+    }
+
+    // `Err` for a script that cannot be read at all
+    fn next_command(
+        &self,
+        command: Result<(usize, String), CliError>,
+        options: &BatchOptions,
+    ) -> Result<Next, CliError> {
+        match command {
+            Ok((line, text)) => {
+                if options.echo {
+                    println!("{}> {}", self.cmd.get_name(), text);
+                }
+                Ok(Next::Run(line, text))
+            }
+            Err(e @ CliError::Script { line, .. }) => Ok(Next::Broken(line, e)),
+            Err(e) => Err(e),
+        }
+    }
+
+    // a command of a batch written down, `Break` when the batch is over: `Ok` for a task
+    // that asked to leave, `Err` for the error it stops at; `start` is when the command
+    // started unless the script was broken there
+    fn batch_ran(
+        &self,
+        report: &mut BatchReport,
+        options: &BatchOptions,
+        line: usize,
+        text: String,
+        r: Result<TaskAction, CliError>,
+        start: Option<Instant>,
+    ) -> ControlFlow<Result<(), CliError>> {
+        if let (true, Some(start)) = (options.timed, start) {
+            report.lines.push(BatchLineReport {
+                line_no: line,
+                text: text.clone(),
+                duration: start.elapsed(),
+                result: r.as_ref().cloned().map_err(|e| e.to_string()),
+            });
+        }
+        match r {
+            Ok(TaskAction::Continue) => {}
+            Ok(action) => {
+                report.action = action;
+                return ControlFlow::Break(Ok(()));
+            }
+            Err(e) => match options.on_error {
+                // a broken script already tells where it is broken
+                ErrorPolicy::Abort => {
+                    if options.timing_summary {
+                        script::print_summary(&report.lines);
+                    }
+                    return ControlFlow::Break(Err(match e {
+                        CliError::Script { .. } => e,
+                        e => CliError::Batch {
+                            line,
+                            text,
+                            source: Box::new(e),
+                        },
+                    }));
+                }
+                ErrorPolicy::Continue => {
+                    self.print_error(&e);
+                    report.errors.push((line, text, e));
+                }
+                ErrorPolicy::Collect => report.errors.push((line, text, e)),
+            },
+        }
+        ControlFlow::Continue(())
+    }
+
+    fn batch_done(&self, report: BatchReport, options: &BatchOptions) -> BatchReport {
+        if options.timing_summary {
+            script::print_summary(&report.lines);
+        }
+        report
+    }
+
+    pub fn run_interactive_with<F: Fn(readline::Builder) -> readline::Builder>(
+        self: &Rc<Self>,
+        ctx: &C,
+        f: F,
+    ) -> Result<TaskAction, CliError> {
+        if !self.prompting() {
+            return self.plain_loop(ctx);
+        }
+        self.load_history();
+        let r = match self.start_rc(ctx)? {
+            Some(action) => Ok(action),
+            None => self.prompt_loop(ctx, &mut self.start_interactive(f)?),
+        };
+        self.end_interactive(r)
+    }
+
+    fn load_history(&self) {
+        if let Err(e) = self.history.borrow_mut().load() {
+            self.print_error(&e);
+        }
+    }
+
+    // the history kept and the farewell said
+    fn end_interactive(&self, r: Result<TaskAction, CliError>) -> Result<TaskAction, CliError> {
+        if let Err(e) = self.history.borrow().save() {
+            self.print_error(&e);
+        }
+        if let Ok(action) = &r {
+            self.say_farewell(action);
+        }
+        r
+    }
+
+    fn say_farewell(&self, action: &TaskAction) {
+        if let Some(f) = &self.farewell {
+            println!("{}", f(action));
+        }
+    }
+
+    // `name> ` unless set
+    fn label(&self) -> String {
+        match &self.prompt_label {
+            Some(l) => l.clone(),
+            None => format!("{}> ", self.cmd.get_name()),
+        }
+    }
+
+    fn record_command(&self, line: &str) {
+        if let Some(path) = &self.transcript {
+            // without a prompt, the label it would have shown
+            let label = match (&*self.shown_label.borrow(), &self.label_fn) {
+                (Some(l), _) => l.clone(),
+                (None, Some(f)) => f(),
+                (None, None) => self.label(),
+            };
+            if let Err(e) = transcript::append(path, &label, "... ", &self.masked(line)) {
+                self.show_error(&e);
+            }
+        }
+    }
+
+    // the line as it may be written down, with the secret values masked
+    fn masked(&self, line: &str) -> String {
+        if self.secrets.is_empty() {
+            return line.to_string();
+        }
+        let mut words = match shlex::split(line) {
+            Some(words) => words,
+            None => return line.to_string(),
+        };
+        let mut hidden = false;
+        for cmd in words.split_mut(|w| ["&&", "||", ";"].contains(&w.as_str())) {
+            hidden |= self.mask_args(cmd);
+        }
+        match hidden {
+            true => words.into_iter().map(quote).collect::<Vec<_>>().join(" "),
+            false => line.to_string(),
+        }
+    }
+
+    // true if any of the words of a command needed masking
+    fn mask_args(&self, words: &mut [String]) -> bool {
+        let (name, args) = match words.split_first_mut() {
+            Some(split) => split,
+            None => return false,
+        };
+        let mut cmd = match self.find_task(name) {
+            Some(t) => t.command(),
+            None => return false,
+        };
+        let ids = match self.secrets.get(cmd.get_name()) {
+            Some(ids) => ids,
+            None => return false,
+        };
+        cmd.build();
+        let secret = |a: &Arg| ids.iter().any(|id| id == a.get_id().as_str());
+        let mut masked = vec![];
+        let (mut i, mut taken, mut raw) = (0, 0, false);
+        while i < args.len() {
+            let t = args[i].as_str();
+            if !raw && is_flag(t) {
+                raw = t == "--";
+                match (find_flag(&cmd, t), t.split_once('=')) {
+                    (Some(a), Some((flag, _))) if secret(a) => {
+                        masked.push((i, format!("{}=****", flag)))
+                    }
+                    (Some(a), None) if a.get_action().takes_values() => {
+                        if secret(a) && i + 1 < args.len() {
+                            masked.push((i + 1, "****".to_string()));
+                        }
+                        i += 1;
+                    }
+                    _ => {}
+                }
+            } else {
+                let a = cmd.get_positionals().nth(taken).or_else(|| {
+                    cmd.get_positionals()
+                        .last()
+                        .filter(|a| a.get_num_args().is_some_and(|n| n.max_values() > 1))
+                });
+                if a.is_some_and(secret) {
+                    masked.push((i, "****".to_string()));
+                }
+                taken += 1;
+            }
+            i += 1;
+        }
+        for (i, text) in &masked {
+            args[*i] = text.clone();
+        }
+        !masked.is_empty()
+    }
+
+    // the action if the rc file already asked to leave
+    fn start_rc(&self, ctx: &C) -> Result<Option<TaskAction>, CliError> {
+        self.rc_done(self.run_rc(ctx))
+    }
+
+    fn rc_done(&self, r: Result<TaskAction, CliError>) -> Result<Option<TaskAction>, CliError> {
+        match r {
+            Ok(TaskAction::Continue) => Ok(None),
+            Ok(action) => Ok(Some(action)),
+            Err(e) if !self.rc_strict => {
+                self.print_error(&e);
+                Ok(None)
+            }
+            Err(e) => Err(e),
+        }
+    }
+
+    fn prompting(&self) -> bool {
+        match self.input_mode {
+            InputMode::Auto => std::io::stdin().is_terminal() && std::io::stdout().is_terminal(),
+            InputMode::Prompt => true,
+            InputMode::Lines => false,
+        }
+    }
+
+    // the commands typed or piped into stdin, without a prompt; no history is kept
+    fn plain_lines(&self) -> impl Iterator<Item = Result<String, CliError>> + '_ {
+        // stdin is locked a line at a time only, a task may read the next one itself
+        let lines = std::iter::from_fn(|| {
+            let mut line = String::new();
+            match std::io::stdin().read_line(&mut line) {
+                Ok(0) => None,
+                Ok(_) => {
+                    let len = line.trim_end_matches(['\r', '\n']).len();
+                    line.truncate(len);
+                    Some(Ok(line))
+                }
+                Err(e) => Some(Err(CliError::Io(e))),
+            }
+        });
+        self.commands(lines, false).map(|c| c.map(|(_, text)| text))
+    }
+
+    fn plain_loop(&self, ctx: &C) -> Result<TaskAction, CliError> {
+        if let Some(action) = self.start_rc(ctx)? {
+            return Ok(action);
+        }
+        let _unattended = input::Unattended::start(self.auto_confirm);
+        for text in self.plain_lines() {
+            let r = {
+                let _watch = self.cancel.watch();
+                text.and_then(|text| self.run_line(ctx, &text))
+            };
+            if let Some(action) = self.line_done(r) {
+                return Ok(action);
+            }
+        }
+        Ok(TaskAction::Exit(0))
+    }
+
+    // the prompt built, once the history is loaded and the rc file run
+    fn start_interactive<F: Fn(readline::Builder) -> readline::Builder>(
+        self: &Rc<Self>,
+        f: F,
+    ) -> Result<promkit::Prompt<State>, CliError> {
+        if self.idle.is_some() {
+            idle::clear_once();
+        }
+        let mut prompt = f(self.prompt())
+            .build()
+            .map_err(|e| CliError::Prompt(e.to_string()))?;
+        if let Some(r) = self.rprompt.clone() {
+            // the first key only comes after the label, so it is drawn with the label too
+            let label = prompt.initialize.take();
+            prompt.initialize = Some(Box::new(move |out, state| {
+                if let Some(label) = &label {
+                    label(out, state)?;
+                }
+                rprompt::draw(out, state, &r(), 0)
+            }));
+        }
+        if let Some(text) = self.banner.as_ref().map(|f| f()) {
+            // after promkit cleared the screen on its first run, before the label
+            let shown = Cell::new(false);
+            let label = prompt.initialize.take();
+            prompt.initialize = Some(Box::new(move |out, state| {
+                if !shown.replace(true) {
+                    // raw mode is on already, every line needs its carriage return
+                    for line in text.lines() {
+                        queue!(out, Print(line), Print("\r\n"))?;
+                    }
+                    out.flush()?;
+                }
+                match &label {
+                    Some(label) => label(out, state),
+                    None => Ok(()),
+                }
+            }));
+        }
+        // no message in between the first drawing and what it drew
+        let printer = self.printer.clone();
+        let label = prompt.initialize.take();
+        prompt.initialize = Some(Box::new(move |out, state| {
+            let mut shown = printer.lock();
+            if let Some(label) = &label {
+                label(out, state)?;
+            }
+            *shown = Some(printer::Drawn::of(state));
+            Ok(())
+        }));
+        Ok(prompt)
+    }
+
+    fn prompt_loop(
+        &self,
+        ctx: &C,
+        prompt: &mut promkit::Prompt<State>,
+    ) -> Result<TaskAction, CliError> {
+        loop {
+            let line = match self.read_line(prompt)? {
+                Input::Line(line) => line,
+                Input::Skip => continue,
+                Input::Eof => break Ok(TaskAction::Exit(0)),
+                Input::Action(action) => match self.line_done(Ok(action)) {
+                    Some(action) => break Ok(action),
+                    None => continue,
+                },
+            };
+            let r = {
+                let _watch = self.cancel.watch();
+                self.run_input(ctx, &line)
+            };
+            if let Some(action) = self.line_done(r) {
+                break Ok(action);
+            }
+        }
+    }
+
+    // the next line to run, history expanded and recorded
+    fn read_line(&self, prompt: &mut promkit::Prompt<State>) -> Result<Input, CliError> {
+        if let Some(h) = prompt.state.1.hstr.as_mut() {
+            self.history.borrow().sync(h);
+        }
+        for ended in self.jobs.borrow_mut().finished() {
+            println!("{}", jobs::report(&ended));
+        }
+        if let Some(f) = &self.label_fn {
+            prompt.state.1.label = Graphemes::from(f());
+        }
+        *self.shown_label.borrow_mut() = Some(prompt.state.1.label.to_string());
+        let read = match &self.idle {
+            Some((timeout, _)) => idle::run_until(prompt, *timeout),
+            None => prompt.run(),
+        };
+        let line = match read {
+            Err(e) if e.kind() == std::io::ErrorKind::TimedOut => return Ok(self.idle_input()),
+            Err(e) if e.kind() == std::io::ErrorKind::UnexpectedEof => return Ok(Input::Eof),
+            Err(e) if e.kind() == std::io::ErrorKind::Interrupted => return Ok(Input::Skip),
+            r => r?,
+        };
+        let line = line.replace(keys::PASTED_NEWLINE, "\n");
+        let label = prompt.state.1.label.clone();
+        let line = Self::read_rest(prompt, line);
+        prompt.state.1.label = label;
+        let line = match line {
+            Ok(Some(line)) => line,
+            Ok(None) => return Ok(Input::Skip),
+            Err(e) => return Err(e.into()),
+        };
+        let expanded = match self.shell_command(&line) {
+            Some(_) => Ok(None),
+            None => self.history.borrow().expand(&line),
+        };
+        let line = match expanded {
+            Ok(Some(l)) => {
+                println!("{}", l);
+                l
+            }
+            Ok(None) => line,
+            Err(e) => {
+                self.print_error(&e);
+                return Ok(Input::Skip);
+            }
+        };
+        let pushed = self
+            .history
+            .borrow_mut()
+            .push(&self.masked(&line), prompt.state.1.limit_history_size);
+        if let Err(e) = pushed {
+            self.print_error(&e);
+        }
+        Ok(Input::Line(line))
+    }
+
+    // a pasted block runs line by line like a script, which joins what a quote spans again
+    fn run_input(&self, ctx: &C, line: &str) -> Result<TaskAction, CliError> {
+        match line.contains('\n') {
+            true => self
+                .run_batch_with(ctx, line, BatchOptions::default().join_quotes(true))
+                .map(|r| r.action),
+            false => self.run_line(ctx, line),
+        }
+    }
+
+    fn idle_input(&self) -> Input {
+        let (timeout, action) = match &self.idle {
+            Some(idle) => idle,
+            None => return Input::Skip,
+        };
+        // no key got to say the prompt is gone
+        *self.printer.lock() = None;
+        println!("idle for {}s", timeout.as_secs());
+        match action {
+            IdleAction::Exit => Input::Action(TaskAction::Exit(0)),
+            IdleAction::RunCommand(line) => Input::Line(line.clone()),
+            IdleAction::Callback(f) => Input::Action(f()),
+        }
+    }
+
+    // the lines that follow one left with an open quote or a trailing backslash, under
+    // a `... ` label; `None` if Ctrl+C or Ctrl+D gave up on them
+    fn read_rest(
+        prompt: &mut promkit::Prompt<State>,
+        mut line: String,
+    ) -> std::io::Result<Option<String>> {
+        loop {
+            let ends = lex::ends(&line);
+            if ends == lex::Ends::Done {
+                return Ok(Some(line));
+            }
+            prompt.state.1.label = Graphemes::from("... ");
+            match prompt.run() {
+                Ok(next) => lex::join(&mut line, ends, &next),
+                Err(e)
+                    if matches!(
+                        e.kind(),
+                        std::io::ErrorKind::Interrupted | std::io::ErrorKind::UnexpectedEof
+                    ) =>
+                {
+                    return Ok(None)
+                }
+                Err(e) => return Err(e),
+            }
+        }
+    }
+
+    // to stderr in the theme's error color, clap's usage errors as clap put them
+    fn print_error(&self, e: &CliError) {
+        if let Some(path) = &self.transcript {
+            if let Err(e) = transcript::append(path, "! ", "! ", &e.to_string()) {
+                self.show_error(&e);
+            }
+        }
+        self.show_error(e);
+    }
+
+    // to the sink or stderr, without the transcript: a transcript that cannot be written
+    // is reported here
+    fn show_error(&self, e: &CliError) {
+        match (&self.error_sink, e) {
+            (Some(f), e) => f(e),
+            (None, CliError::Clap(_)) => eprintln!("{}", e),
+            // no escapes into a log file or a pipe
+            (None, e) if !std::io::stderr().is_terminal() => eprintln!("{}", e),
+            (None, e) => eprintln!("{}", theme::paint(e, self.theme.error)),
+        }
+    }
+
+    // the action that ends the loop, if the line asked for one
+    fn line_done(&self, r: Result<TaskAction, CliError>) -> Option<TaskAction> {
+        let action = r.unwrap_or_else(|e| {
+            self.print_error(&e);
+            TaskAction::Continue
+        });
+        // over the `^C` the terminal may have echoed already
+        if self.cancel.is_cancelled() {
+            println!("\r^C");
+        }
+        (action != TaskAction::Continue).then_some(action)
+    }
+
+    pub fn run_interactive(self: &Rc<Self>, ctx: &C) -> Result<TaskAction, CliError> {
+        self.run_interactive_with(ctx, |b| b)
+    }
+}
+
+impl<C: 'static> Task<C> for Cli<C> {
+    fn command(&self) -> Command {
+        // strip out name/version
+        const APPLET_TEMPLATE: &str = "\
+                {about-with-newline}\n\
+                {usage-heading}\n    {usage}\n\
+                \n\
+                {all-args}{after-help}\
+            ";
+        self.root
+            .get_or_init(|| {
+                self.tasks().fold(self.cmd.clone(), |c, t| {
+                    c.subcommand(t.command().help_template(APPLET_TEMPLATE))
+                })
+            })
+            .clone()
+    }
+    fn action(&self, ctx: &C, matches: &ArgMatches) -> TaskAction {
+        self.action_raw(ctx, matches, None)
+    }
+    fn action_raw(&self, ctx: &C, matches: &ArgMatches, raw: Option<&str>) -> TaskAction {
+        let (name, matches) = matches.subcommand().unwrap();
+        match self.own(name, matches) {
+            Some(Own::Source) => self.dispatch(name, matches, || self.source(ctx, matches)),
+            Some(Own::Repeat(count, cmd)) => self.dispatch(name, matches, || {
+                self.repeat(ctx, count, &cmd)
+                    .unwrap_or_else(|e| TaskAction::Error(e.to_string()))
+            }),
+            None => self.dispatch(name, matches, || {
+                self.cmds[name].action_raw(ctx, matches, raw)
+            }),
+        }
+    }
+    fn suggests(&self, args: &[&'_ str]) -> Option<Graphemes> {
+        let (name, rest) = args.split_first()?;
+        if after_raw(&args[..args.len() - 1]) {
+            return None;
+        }
+        if let Some((count, cmd)) = self.repeated(name, rest) {
+            return self
+                .suggests(cmd)
+                .map(|s| Graphemes::from(format!("{} {} {}", name, count, s)));
+        }
+        if let Some((head, candidates)) = self.variable_completion(args) {
+            let last = args[args.len() - 1];
+            return Some(
+                self.matcher
+                    .scope(|| join_replacement(head, candidates, last)),
+            );
+        }
+        self.matcher.scope(|| {
+            if let Some(topic) = self.help_topic(name, rest) {
+                return Some(Graphemes::from(format!(
+                    "{} {}",
+                    name,
+                    quote(complete(self.names(), topic).to_string())
+                )));
+            }
+            self.find_task(name)
+                .and_then(|c| {
+                    self.providers
+                        .get(c.command().get_name())
+                        .and_then(|p| p(rest))
+                        .or_else(|| {
+                            self.dynamic_completion(c, rest).map(|(head, candidates)| {
+                                join_replacement(head, candidates, rest[rest.len() - 1])
+                            })
+                        })
+                        .or_else(|| c.suggests(rest))
+                        .or_else(|| complete_args(&c.command(), rest))
+                        .map(|r| {
+                            Graphemes::from([quote(name.to_string()), r.to_string()].join(" "))
+                        })
+                })
+                .or_else(|| {
+                    // the root takes no flags, so a flag can never start the line
+                    if !rest.is_empty() || name.starts_with('-') {
+                        return None;
+                    }
+                    Some(complete(self.first_words(), name))
+                })
+        })
+    }
+    fn candidates(&self, args: &[&'_ str]) -> Vec<Graphemes> {
+        let (name, rest) = match args.split_first() {
+            Some(s) => s,
+            None => return vec![],
+        };
+        if after_raw(&args[..args.len() - 1]) {
+            return vec![];
+        }
+        if let Some((count, cmd)) = self.repeated(name, rest) {
+            return self
+                .candidates(cmd)
+                .into_iter()
+                .map(|c| Graphemes::from(format!("{} {} {}", name, count, c)))
+                .collect();
+        }
+        if let Some((head, candidates)) = self.variable_completion(args) {
+            return join_candidates(head, candidates);
+        }
+        if let Some(topic) = self.help_topic(name, rest) {
+            return self.matcher.scope(|| {
+                self.matcher
+                    .rank(self.names(), topic)
+                    .into_iter()
+                    .map(|t| Graphemes::from(format!("{} {}", name, quote(t))))
+                    .collect()
+            });
+        }
+        self.matcher.scope(|| match self.find_task(name) {
+            Some(c) if !rest.is_empty() => self
+                .dynamic_completion(c, rest)
+                .map(|(head, candidates)| join_candidates(head, candidates))
+                .unwrap_or_else(|| c.candidates(rest))
+                .into_iter()
+                .map(|r| Graphemes::from([quote(name.to_string()), r.to_string()].join(" ")))
+                .collect(),
+            _ if rest.is_empty() && !name.starts_with('-') => self
+                .matcher
+                .rank(self.first_words(), name)
+                .into_iter()
+                .map(Graphemes::from)
+                .collect(),
+            _ => vec![],
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    // the line after Tab with the cursor at its end, `None` if nothing changed
+    fn tab<C: 'static>(cli: &Cli<C>, line: &str) -> Option<String> {
+        cli.complete_at(&mut Cycle::default(), line, line.chars().count(), true)
+            .map(|(r, _)| r)
+    }
+
+    fn noop(_: &ArgMatches) -> TaskAction {
+        TaskAction::Continue
+    }
+
+    // a cli with a `tick` task counting its runs
+    fn ticking() -> (Cli, Arc<AtomicUsize>) {
+        let ticks = Arc::new(AtomicUsize::new(0));
+        let counted = ticks.clone();
+        let cli = Cli::new("test").add_fn_task(Command::new("tick"), move |_| {
+            counted.fetch_add(1, Ordering::SeqCst);
+            TaskAction::Continue
+        });
+        (cli, ticks)
+    }
+
+    #[test]
+    fn repeat_from_args() {
+        let (cli, ticks) = ticking();
+        let cli = cli.allow_repeat(true);
+        let action = cli.run_args(&(), ["repeat", "3", "tick"]).unwrap();
+        assert_eq!(action, TaskAction::Continue);
+        assert_eq!(ticks.load(Ordering::SeqCst), 3);
+    }
+
+    #[test]
+    fn repeat_with_a_variable_count() {
+        let (cli, ticks) = ticking();
+        let cli = cli.allow_repeat(true).variables(true);
+        cli.run_batch(&(), "set N=2\nrepeat $N tick").unwrap();
+        assert_eq!(ticks.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    #[should_panic(expected = "there is a command 'history' already")]
+    fn builtins_do_not_replace_a_task() {
+        let _ = Cli::new("test")
+            .add_fn_task(Command::new("history"), |_| TaskAction::Continue)
+            .with_builtins();
+    }
+
+    // a `name` task of the user's with no args, and whether it ran
+    fn user_task(
+        name: &str,
+    ) -> (
+        fn_task::FnTask<impl Fn(&ArgMatches) -> TaskAction>,
+        Rc<Cell<bool>>,
+    ) {
+        let ran = Rc::new(Cell::new(false));
+        let seen = ran.clone();
+        let f = move |_: &ArgMatches| {
+            seen.set(true);
+            TaskAction::Continue
+        };
+        let cmd = Command::new(name.to_string());
+        (fn_task::FnTask { cmd, f }, ran)
+    }
+
+    #[test]
+    fn replaced_source_runs_as_a_task() {
+        let (task, ran) = user_task("source");
+        let cli = Cli::new("test").with_builtins().replace_task(task);
+        assert_eq!(cli.run(&(), "source").unwrap(), TaskAction::Continue);
+        assert!(ran.get());
+    }
+
+    #[test]
+    fn replaced_repeat_runs_as_a_task() {
+        let (task, ran) = user_task("repeat");
+        let cli = Cli::new("test").with_builtins().replace_task(task);
+        assert_eq!(cli.run(&(), "repeat").unwrap(), TaskAction::Continue);
+        assert!(ran.get());
+        // nor is a line like the builtin's taken from it
+        assert!(matches!(
+            cli.run(&(), "repeat 2 history"),
+            Err(CliError::Clap(_))
+        ));
+    }
+
+    #[test]
+    fn every_builtin_can_be_replaced() {
+        let names = Cli::new("test").with_builtins().builtins;
+        assert!(names.contains("source") && names.contains("repeat"));
+        for name in names {
+            let (task, ran) = user_task(&name);
+            let cli = Cli::new("test").with_builtins().replace_task(task);
+            assert_eq!(
+                cli.run(&(), &name).unwrap(),
+                TaskAction::Continue,
+                "{}",
+                name
+            );
+            assert!(ran.get(), "{}", name);
+        }
+    }
+
+    #[test]
+    fn builtins_turned_on_twice() {
+        let cli = Cli::new("test").variables(true).with_builtins();
+        assert!(cli.run(&(), "set A=1").is_ok());
+    }
+
+    #[test]
+    fn duplicate_names_are_rejected() {
+        let task = |cmd: Command| StatefulTask::new(cmd, (), |_, _| TaskAction::Continue);
+        let cli = Cli::new("test").add_task(task(Command::new("status").alias("st")));
+        assert!(matches!(
+            cli.try_add_task(task(Command::new("st"))),
+            Err(CliError::DuplicateCommand(name)) if name == "st"
+        ));
+        assert!(matches!(
+            Cli::new("test").try_add_task(task(Command::new("help"))),
+            Err(CliError::DuplicateCommand(name)) if name == "help"
+        ));
+    }
+
+    #[test]
+    fn stray_quote_fails_on_its_own_line() {
+        let (cli, ticks) = ticking();
+        let options = BatchOptions::default().on_error(ErrorPolicy::Collect);
+        let report = cli
+            .run_batch_with(
+                &(),
+                "tick 'x
+tick
+tick",
+                options,
+            )
+            .unwrap();
+        assert_eq!(report.errors.len(), 1);
+        assert_eq!(report.errors[0].0, 1);
+        assert_eq!(ticks.load(Ordering::SeqCst), 2);
+    }
+
+    struct Push;
+
+    impl Task<RefCell<Vec<String>>> for Push {
+        fn command(&self) -> Command {
+            Command::new("push").arg(Arg::new("word").required(true))
+        }
+        fn action(&self, ctx: &RefCell<Vec<String>>, matches: &ArgMatches) -> TaskAction {
+            let word = matches.get_one::<String>("word").unwrap();
+            ctx.borrow_mut().push(word.clone());
+            TaskAction::Continue
+        }
+    }
+
+    #[test]
+    fn tasks_share_the_context_of_the_run() {
+        let cli = Cli::for_context("test")
+            .add_task(Push)
+            .allow_repeat(true)
+            .add_fn_task(Command::new("tick"), |_| TaskAction::Continue);
+        let words = RefCell::new(vec![]);
+        cli.run(&words, "push a").unwrap();
+        cli.run_batch(&words, "tick && push b").unwrap();
+        cli.run_args(&words, ["repeat", "2", "push", "c"]).unwrap();
+        cli.run(&words, "repeat 1 push d").unwrap();
+        assert_eq!(*words.borrow(), ["a", "b", "c", "c", "d"]);
+        // another run, another context
+        let other = RefCell::new(vec![]);
+        cli.run(&other, "push e").unwrap();
+        assert_eq!(*other.borrow(), ["e"]);
+    }
+
+    #[test]
+    fn transcript_records_the_label_shown() {
+        let path = std::env::temp_dir().join(format!("icli-transcript-{}", std::process::id()));
+        let (cli, _) = ticking();
+        let cli = cli
+            .prompt_label_fn(|| "[dev] ".to_string())
+            .transcript(&path);
+        cli.run_batch(&(), "tick").unwrap();
+        let written = std::fs::read_to_string(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+        assert!(written.trim_end().ends_with("] [dev] tick"), "{}", written);
+    }
+
+    #[test]
+    fn transcript_errors_reach_the_sink() {
+        let errors = Arc::new(std::sync::Mutex::new(vec![]));
+        let seen = errors.clone();
+        let (cli, ticks) = ticking();
+        let cli = cli
+            .transcript(
+                std::env::temp_dir()
+                    .join("icli-no-such-dir")
+                    .join("transcript"),
+            )
+            .error_sink(move |e| seen.lock().unwrap().push(e.to_string()));
+        cli.run_batch(&(), "tick").unwrap();
+        // the command still runs
+        assert_eq!(ticks.load(Ordering::SeqCst), 1);
+        let errors = errors.lock().unwrap();
+        assert_eq!(errors.len(), 1, "{:?}", errors);
+        assert!(errors[0].contains("icli-no-such-dir"), "{:?}", errors);
+    }
+
+    #[test]
+    fn possible_values_complete() {
+        let run = Command::new("run")
+            .arg(
+                Arg::new("mode")
+                    .long("mode")
+                    .value_parser(["fast", "slow", "auto"]),
+            )
+            .arg(Arg::new("level").value_parser(["low", "high"]));
+        let cli = Cli::new("test").add_fn_task(run, noop);
+        assert_eq!(
+            tab(&cli, "run --mode f").as_deref(),
+            Some("run --mode fast")
+        );
+        assert_eq!(
+            tab(&cli, "run --mode=s").as_deref(),
+            Some("run --mode=slow")
+        );
+        assert_eq!(tab(&cli, "run h").as_deref(), Some("run high"));
+        // the flag and its value are no positional
+        assert_eq!(
+            tab(&cli, "run --mode fast l").as_deref(),
+            Some("run --mode fast low")
+        );
+        assert_eq!(tab(&cli, "run --mode x"), None);
+    }
+
+    // nothing but its command, completion comes from the default methods
+    struct Net;
+
+    impl Task for Net {
+        fn command(&self) -> Command {
+            Command::new("net")
+                .subcommand(
+                    Command::new("ping")
+                        .arg(Arg::new("proto").long("proto").value_parser(["tcp", "udp"])),
+                )
+                .subcommand(Command::new("trace"))
+        }
+        fn action(&self, _: &(), _: &ArgMatches) -> TaskAction {
+            TaskAction::Continue
+        }
+    }
+
+    #[test]
+    fn default_suggests_follow_the_command() {
+        let suggests = |args: &[&str]| Net.suggests(args).map(|g| g.to_string());
+        assert_eq!(suggests(&["p"]).as_deref(), Some("ping"));
+        assert_eq!(suggests(&["ping", "--p"]).as_deref(), Some("ping --proto"));
+        assert_eq!(
+            suggests(&["ping", "--proto", "u"]).as_deref(),
+            Some("ping --proto udp")
+        );
+        let cli = Cli::new("test").add_task(Net);
+        assert_eq!(tab(&cli, "net t").as_deref(), Some("net trace"));
+    }
+
+    struct Fixed;
+
+    impl Task for Fixed {
+        fn command(&self) -> Command {
+            Command::new("fixed").arg(Arg::new("what"))
+        }
+        fn action(&self, _: &(), _: &ArgMatches) -> TaskAction {
+            TaskAction::Continue
+        }
+        fn suggests(&self, _: &[&'_ str]) -> Option<Graphemes> {
+            Some(Graphemes::from("always"))
+        }
+    }
+
+    #[test]
+    fn own_suggests_win() {
+        let cli = Cli::new("test").add_task(Fixed);
+        assert_eq!(tab(&cli, "fixed a").as_deref(), Some("fixed always"));
+    }
+
+    #[test]
+    fn complete_extends_to_the_common_prefix() {
+        let names = || ["status", "stop", "start"].map(String::from);
+        assert_eq!(complete(names(), "s").to_string(), "st");
+        assert_eq!(complete(names(), "sta").to_string(), "sta");
+        assert_eq!(complete(names(), "stat").to_string(), "status");
+        assert_eq!(complete(names(), "x").to_string(), "x");
+        let cli = ["status", "stop", "start"]
+            .into_iter()
+            .fold(Cli::new("test"), |cli, n| {
+                cli.add_fn_task(Command::new(n), noop)
+            });
+        assert_eq!(tab(&cli, "s").as_deref(), Some("st"));
+    }
+
+    fn notes() -> Cli {
+        let add = Command::new("add")
+            .arg(Arg::new("title").required(true))
+            .arg(Arg::new("tag").long("tag"));
+        Cli::new("test").add_fn_task(Command::new("note").subcommand(add), noop)
+    }
+
+    #[test]
+    fn quoted_args_survive_completion() {
+        let cli = notes();
+        let line = tab(&cli, r#"note add "my title" --t"#).unwrap();
+        assert_eq!(line, "note add 'my title' --tag");
+        let matches = cli.parse(&format!("{} x", line)).unwrap().unwrap();
+        let (_, note) = matches.subcommand().unwrap();
+        let (_, add) = note.subcommand().unwrap();
+        assert_eq!(add.get_one::<String>("title").unwrap(), "my title");
+    }
+
+    #[test]
+    fn fuzzy_mode_takes_the_best_match() {
+        let cli = ["config-set", "config-get", "cancel"]
+            .into_iter()
+            .fold(Cli::new("test"), |cli, n| {
+                cli.add_fn_task(Command::new(n), noop)
+            })
+            .completion_mode(CompletionMode::Fuzzy);
+        assert_eq!(tab(&cli, "cfgset").as_deref(), Some("config-set"));
+        assert_eq!(tab(&cli, "cg").as_deref(), Some("config-get"));
+    }
+
+    #[test]
+    fn help_completes_task_names() {
+        let cli = Cli::new("test")
+            .add_fn_task(Command::new("status").alias("info"), noop)
+            .add_fn_task(Command::new("stop"), noop);
+        assert_eq!(tab(&cli, "help sta").as_deref(), Some("help status"));
+        assert_eq!(tab(&cli, "help in").as_deref(), Some("help info"));
+        assert_eq!(tab(&cli, "help he").as_deref(), Some("help help"));
+        // one topic only
+        assert_eq!(tab(&cli, "help help s"), None);
+    }
+
+    #[test]
+    fn nested_subcommands_complete() {
+        let ip = Command::new("ip")
+            .subcommand(Command::new("addr").arg(Arg::new("family").long("family")))
+            .subcommand(Command::new("route"));
+        let net = Command::new("net")
+            .subcommand(ip)
+            .subcommand(Command::new("ping"));
+        let cli = Cli::new("test").add_fn_task(Command::new("dev").subcommand(net), noop);
+        assert_eq!(tab(&cli, "dev n").as_deref(), Some("dev net"));
+        assert_eq!(tab(&cli, "dev net i").as_deref(), Some("dev net ip"));
+        assert_eq!(
+            tab(&cli, "dev net ip r").as_deref(),
+            Some("dev net ip route")
+        );
+        assert_eq!(
+            tab(&cli, "dev net ip addr --f").as_deref(),
+            Some("dev net ip addr --family")
+        );
+    }
+
+    #[test]
+    fn flags_do_not_start_a_line() {
+        let cli = Cli::new("test")
+            .add_fn_task(Command::new("verbose"), noop)
+            .add_fn_task(Command::new("version"), noop);
+        for line in ["-", "--", "-v", "--ver"] {
+            assert_eq!(tab(&cli, line), None, "{}", line);
+        }
+    }
+
+    #[test]
+    fn completed_values_are_quoted() {
+        let names = ["My Documents", "it's", r"a\b", "#1"];
+        let cli = Cli::new("test")
+            .add_fn_task(Command::new("open").arg(Arg::new("path")), noop)
+            .register_completer("open", "path", move |_| {
+                names.iter().map(|n| n.to_string()).collect()
+            });
+        for (typed, name) in [
+            ("My", "My Documents"),
+            ("it", "it's"),
+            ("a", r"a\b"),
+            ("#", "#1"),
+        ] {
+            let line = tab(&cli, &format!("open {}", typed)).unwrap();
+            let matches = cli.parse(&line).unwrap().unwrap();
+            let (_, open) = matches.subcommand().unwrap();
+            assert_eq!(open.get_one::<String>("path").unwrap(), name, "{}", line);
+        }
+    }
+
+    #[test]
+    fn completion_at_the_cursor() {
+        let cli = Cli::new("test").add_fn_task(
+            Command::new("status").arg(Arg::new("verbose").long("verbose")),
+            noop,
+        );
+        let mut cycle = Cycle::default();
+        assert_eq!(
+            cli.complete_at(&mut cycle, "stat --verbose", 4, true),
+            Some(("status --verbose".to_string(), 6))
+        );
+        assert_eq!(split_at_cursor("é x", 1), ("é", " x"));
+        assert_eq!(split_at_cursor("ab", 9), ("ab", ""));
+    }
+
+    #[test]
+    fn unknown_commands_get_did_you_mean() {
+        let cli = Cli::new("test")
+            .add_fn_task(Command::new("status").alias("info"), noop)
+            .add_fn_task(Command::new("stop"), noop);
+        let e = cli.run(&(), "staus").unwrap_err();
+        assert!(e.to_string().contains("'status'"), "{}", e);
+        assert!(matches!(
+            cli.run(&(), "inff"),
+            Err(CliError::UnknownCommand { suggestions, .. }) if suggestions == ["info"]
+        ));
+        assert!(matches!(
+            cli.run(&(), "qwertyuiop"),
+            Err(CliError::UnknownCommand { suggestions, .. }) if suggestions.is_empty()
+        ));
+    }
+
+    fn pushing() -> Cli<RefCell<Vec<String>>> {
+        Cli::for_context("test").add_task(Push)
+    }
+
+    #[test]
+    fn batch_comments_are_skipped() {
+        let words = RefCell::new(vec![]);
+        let script = "# a header\n\npush a # trailing\n  # indented\npush \"#notacomment\"\n";
+        pushing().run_batch(&words, script).unwrap();
+        assert_eq!(*words.borrow(), ["a", "#notacomment"]);
+    }
+
+    #[test]
+    fn crlf_scripts_run_like_any_other() {
+        let words = RefCell::new(vec![]);
+        let script = "push a\r\npush b; push c\r\n";
+        pushing().run_batch(&words, script).unwrap();
+        pushing()
+            .run_batch_from_reader(&words, script.as_bytes())
+            .unwrap();
+        assert_eq!(*words.borrow(), ["a", "b", "c", "a", "b", "c"]);
+    }
+
+    #[test]
+    fn quoted_semicolons_stay_in_their_word() {
+        let words = RefCell::new(vec![]);
+        let script = "push \"hello; world\"; push 'a;b'\npush c\\;d";
+        pushing().run_batch(&words, script).unwrap();
+        assert_eq!(*words.borrow(), ["hello; world", "a;b", "c;d"]);
+    }
+
+    #[test]
+    fn chains_short_circuit() {
+        let cli =
+            pushing().add_fn_task(Command::new("fail"), |_| TaskAction::Error("failed".into()));
+        let words = RefCell::new(vec![]);
+        let script = "push a && fail && push b || push c; push d\nfail || push e && push f";
+        cli.run_batch(&words, script).unwrap();
+        assert_eq!(*words.borrow(), ["a", "c", "d", "e", "f"]);
+        // the last command decides how the line went
+        assert!(cli.run_batch(&words, "push g || fail && fail").is_err());
+    }
+
+    #[test]
+    fn exit_codes_come_back_from_a_batch() {
+        let quit = Command::new("quit").arg(
+            Arg::new("code")
+                .required(true)
+                .value_parser(clap::value_parser!(i32)),
+        );
+        let (cli, ticks) = ticking();
+        let cli = cli.add_fn_task(quit, |m| {
+            TaskAction::Exit(*m.get_one::<i32>("code").unwrap())
+        });
+        let action = cli.run_batch(&(), "tick\nquit 3\ntick").unwrap();
+        assert_eq!(exit_code(&action), 3);
+        assert_eq!(ticks.load(Ordering::SeqCst), 1);
+        assert_eq!(exit_code(&cli.run_batch(&(), "tick").unwrap()), 0);
+        assert_eq!(exit_code(&TaskAction::Error("no".into())), 1);
+        assert_eq!(exit_code(&TaskAction::Break), 0);
+    }
+
+    // what `#[derive(Parser)]` would write for it, clap's derive is not a dependency
+    #[derive(Debug, PartialEq)]
+    struct Deploy {
+        force: bool,
+        region: Option<String>,
+        target: String,
+        rollback: bool,
+    }
+
+    impl clap::CommandFactory for Deploy {
+        fn command() -> Command {
+            Command::new("deploy")
+                .arg(Arg::new("force").long("force").action(ArgAction::SetTrue))
+                .arg(Arg::new("region").long("region"))
+                .arg(Arg::new("target").required(true))
+                .subcommand(Command::new("rollback"))
+        }
+        fn command_for_update() -> Command {
+            Self::command()
+        }
+    }
+
+    impl clap::FromArgMatches for Deploy {
+        fn from_arg_matches(m: &ArgMatches) -> Result<Self, clap::Error> {
+            Ok(Deploy {
+                force: m.get_flag("force"),
+                region: m.get_one::<String>("region").cloned(),
+                target: m.get_one::<String>("target").cloned().ok_or_else(|| {
+                    clap::Error::new(clap::error::ErrorKind::MissingRequiredArgument)
+                })?,
+                rollback: m.subcommand_name() == Some("rollback"),
+            })
+        }
+        fn update_from_arg_matches(&mut self, m: &ArgMatches) -> Result<(), clap::Error> {
+            *self = Self::from_arg_matches(m)?;
+            Ok(())
+        }
+    }
+
+    impl clap::Parser for Deploy {}
+
+    #[test]
+    fn typed_args_round_trip() {
+        let got = Rc::new(RefCell::new(None));
+        let seen = got.clone();
+        let cli =
+            Cli::new("test").add_fn_task(<Deploy as clap::CommandFactory>::command(), move |m| {
+                match from_matches::<Deploy>(m) {
+                    Ok(d) => *seen.borrow_mut() = Some(d),
+                    Err(e) => return TaskAction::Error(e.to_string()),
+                }
+                TaskAction::Continue
+            });
+        let line = "deploy --force --region 'eu west' prod rollback";
+        cli.run(&(), line).unwrap();
+        let expected = Deploy {
+            force: true,
+            region: Some("eu west".into()),
+            target: "prod".into(),
+            rollback: true,
+        };
+        assert_eq!(got.borrow().as_ref(), Some(&expected));
+        assert_eq!(cli.parse_into::<Deploy>(line).unwrap(), expected);
+        assert!(matches!(
+            cli.parse_into::<Deploy>("deploy --force"),
+            Err(CliError::Clap(_))
+        ));
+    }
+
+    #[test]
+    fn usage_hint_shows_what_is_left() {
+        let connect = Command::new("connect")
+            .arg(Arg::new("host").required(true).value_name("HOST"))
+            .arg(Arg::new("port").long("port"))
+            .arg(Arg::new("tls").long("tls").action(ArgAction::SetTrue));
+        let remote = Command::new("remote")
+            .subcommand_required(true)
+            .subcommand(Command::new("add").arg(Arg::new("name").required(true)))
+            .subcommand(Command::new("list"));
+        let cli = Cli::new("test")
+            .add_fn_task(connect, noop)
+            .add_fn_task(remote, noop)
+            .add_fn_task(Command::new("quit"), noop);
+        let hint = |line: &str| cli.usage_hint(line);
+        assert_eq!(
+            hint("connect ").as_deref(),
+            Some("<HOST> [--port <PORT>] [--tls]")
+        );
+        // what is typed drops out
+        assert_eq!(
+            hint("connect db --tls ").as_deref(),
+            Some("[--port <PORT>]")
+        );
+        assert_eq!(hint("remote ").as_deref(), Some("<COMMAND>"));
+        assert_eq!(hint("remote add ").as_deref(), Some("<NAME>"));
+        // nothing left, not done typing, or no task
+        assert_eq!(hint("quit "), None);
+        assert_eq!(hint("connect"), None);
+        assert_eq!(hint("nope "), None);
+    }
+
+    #[test]
+    fn aliases_resolve_to_their_task() {
+        let ran = Rc::new(Cell::new(false));
+        let seen = ran.clone();
+        let list = Command::new("list")
+            .visible_alias("ls")
+            .arg(Arg::new("all").long("all").action(ArgAction::SetTrue));
+        let cli = Cli::new("test").add_fn_task(list, move |m| {
+            seen.set(m.get_flag("all"));
+            TaskAction::Continue
+        });
+        assert_eq!(tab(&cli, "ls --a").as_deref(), Some("ls --all"));
+        assert_eq!(cli.usage_hint("ls ").as_deref(), Some("[--all]"));
+        cli.run(&(), "ls --all").unwrap();
+        assert!(ran.get());
+    }
+
+    // `calc` writes down the rest of its line
+    struct Calc(Rc<RefCell<Vec<String>>>);
+
+    impl RawTask for Calc {
+        fn name(&self) -> &str {
+            "calc"
+        }
+        fn action(&self, rest: &str) -> TaskAction {
+            self.0.borrow_mut().push(rest.to_string());
+            TaskAction::Continue
+        }
+    }
+
+    #[test]
+    fn raw_tasks_take_the_rest_of_the_line() {
+        let got = Rc::new(RefCell::new(vec![]));
+        let (cli, ticks) = ticking();
+        let cli = cli.add_raw_task(Calc(got.clone())).aliases(true);
+        cli.run_line(&(), "tick; calc 1;2 && x || y &").unwrap();
+        cli.run_batch(&(), "tick && calc a; 'b\ncalc c").unwrap();
+        cli.run_batch(&(), "alias c calc\nc it's").unwrap();
+        assert_eq!(*got.borrow(), ["1;2 && x || y &", "a; 'b", "c", "it's"]);
+        assert_eq!(ticks.load(Ordering::SeqCst), 2);
+    }
+
+    #[test]
+    fn prompt_settings_reach_the_prompt() {
+        let built = |cli: Cli| Rc::new(cli).prompt().build().unwrap().state;
+        let state = built(
+            Cli::new("test")
+                .prompt_label("dev$ ")
+                .prompt_color(Color::Cyan)
+                .history_limit(50),
+        );
+        assert_eq!(state.1.label.to_string(), "dev$ ");
+        assert_eq!(state.1.label_color, Color::Cyan);
+        assert_eq!(state.1.limit_history_size, Some(50));
+        let defaults = built(Cli::new("test"));
+        assert_eq!(defaults.1.label.to_string(), "test> ");
+        assert_eq!(defaults.1.limit_history_size, Some(1000));
+    }
+
+    // a task that only has its command
+    struct Named(Command);
+
+    impl Task for Named {
+        fn command(&self) -> Command {
+            self.0.clone()
+        }
+        fn action(&self, _: &(), _: &ArgMatches) -> TaskAction {
+            TaskAction::Continue
+        }
+    }
+
+    #[test]
+    fn command_name_hints_complete_the_tasks() {
+        let which = Command::new("which").arg(Arg::new("name").value_hint(ValueHint::CommandName));
+        let cli = Cli::new("test")
+            .add_task(Named(which))
+            .add_task(Named(Command::new("tick")));
+        assert_eq!(tab(&cli, "which ti").as_deref(), Some("which tick"));
+    }
+
+    #[test]
+    fn hint_sources_replace_the_built_in_values() {
+        let ssh = Command::new("ssh").arg(Arg::new("host").value_hint(ValueHint::Hostname));
+        let cli = Cli::new("test")
+            .add_task(Named(ssh))
+            .hint_source(ValueHint::Hostname, |_| vec!["alpha".to_string()]);
+        assert_eq!(tab(&cli, "ssh al").as_deref(), Some("ssh alpha"));
+    }
+
+    #[test]
+    fn variables_complete_after_a_dollar() {
+        let echo = Command::new("echo").arg(Arg::new("text"));
+        let cli = Cli::new("test")
+            .add_task(Named(echo))
+            .variable_source(|_| vec!["HOME".to_string(), "PATH".to_string()]);
+        assert_eq!(tab(&cli, "echo $HO").as_deref(), Some("echo $HOME"));
+    }
+
+    #[test]
+    fn an_aborted_batch_tells_the_failing_line() {
+        let cli = Cli::new("test").add_task(Named(Command::new("tick")));
+        let e = cli
+            .run_batch_with(&(), "tick\ntick; nope\ntick", BatchOptions::default())
+            .unwrap_err();
+        assert!(matches!(e, CliError::Batch { line: 2, ref text, .. } if text == "nope"));
+    }
+
+    #[test]
+    fn a_collecting_batch_runs_to_its_end() {
+        let cli = Cli::new("test").add_task(Named(Command::new("tick")));
+        let options = BatchOptions::default().on_error(ErrorPolicy::Collect);
+        let report = cli
+            .run_batch_with(&(), "nope\ntick\nnope 2", options)
+            .unwrap();
+        assert_eq!(report.action, TaskAction::Continue);
+        let failed = report.errors.iter().map(|(l, t, _)| (*l, t.as_str()));
+        assert_eq!(failed.collect::<Vec<_>>(), [(1, "nope"), (3, "nope 2")]);
+    }
+
+    #[test]
+    fn set_and_unset_change_what_expands() {
+        let cli = Cli::new("test").variables(true);
+        cli.run_batch(&(), "set ICLI_A=1 ICLI_B=x=y").unwrap();
+        assert_eq!(cli.variable("ICLI_A").as_deref(), Some("1"));
+        assert_eq!(cli.variable("ICLI_B").as_deref(), Some("x=y"));
+        cli.run_batch(&(), "set ICLI_C=$ICLI_A\nunset ICLI_A")
+            .unwrap();
+        assert_eq!(cli.variable("ICLI_C").as_deref(), Some("1"));
+        assert_eq!(cli.variable("ICLI_A"), None);
+    }
+
+    // a `tick` task counting its runs
+    struct Ticks(Rc<Cell<usize>>);
+
+    impl Task for Ticks {
+        fn command(&self) -> Command {
+            Command::new("tick").arg(Arg::new("times").value_parser(clap::value_parser!(u8)))
+        }
+        fn action(&self, _: &(), _: &ArgMatches) -> TaskAction {
+            self.0.set(self.0.get() + 1);
+            TaskAction::Continue
+        }
+    }
+
+    #[test]
+    fn validating_finds_every_problem_without_running() {
+        let ticks = Rc::new(Cell::new(0));
+        let cli = Cli::new("test").add_task(Ticks(ticks.clone()));
+        assert_eq!(cli.validate_batch("tick\ntick 2 && tick --help"), Ok(()));
+        let problems = cli
+            .validate_batch("tick x\ntick\nnope; tick &&\ntick \\")
+            .unwrap_err();
+        let lines = problems.iter().map(|(l, _)| *l).collect::<Vec<_>>();
+        assert_eq!(lines, [1, 3, 3, 4]);
+        assert_eq!(ticks.get(), 0);
+    }
+
+    #[test]
+    fn a_timed_batch_reports_every_command() {
+        let cli = Cli::new("test").add_task(Named(Command::new("tick")));
+        let options = BatchOptions::default()
+            .on_error(ErrorPolicy::Collect)
+            .timed(true);
+        let report = cli
+            .run_batch_with(&(), "tick\nnope; tick", options)
+            .unwrap();
+        let lines = report
+            .lines
+            .iter()
+            .map(|l| (l.line_no, l.text.as_str(), l.result.is_ok()))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            lines,
+            [(1, "tick", true), (2, "nope", false), (2, "tick", true)]
+        );
+    }
+
+    #[test]
+    fn aliases_run_and_complete_like_tasks() {
+        let ticks = Rc::new(Cell::new(0));
+        let cli = Cli::new("test")
+            .add_task(Ticks(ticks.clone()))
+            .aliases(true);
+        cli.run_batch(&(), "alias twice 'tick 2'\ntwice; twice")
+            .unwrap();
+        assert_eq!(ticks.get(), 2);
+        assert_eq!(tab(&cli, "tw").as_deref(), Some("twice"));
+        cli.run_batch(&(), "unalias twice").unwrap();
+        assert!(cli.run(&(), "twice").is_err());
+        assert_eq!(
+            cli.run(&(), "unalias twice").unwrap(),
+            TaskAction::Error("no alias 'twice'".into())
+        );
+    }
+
+    // a `deploy` task asking whether to go on
+    struct Asks(Rc<Cell<Option<bool>>>);
+
+    impl Task for Asks {
+        fn command(&self) -> Command {
+            Command::new("deploy")
+        }
+        fn action(&self, _: &(), _: &ArgMatches) -> TaskAction {
+            self.0.set(confirm("sure?", false).ok());
+            TaskAction::Continue
+        }
+    }
+
+    #[test]
+    fn batches_answer_confirm_themselves() {
+        let answer = Rc::new(Cell::new(None));
+        let cli = Cli::new("test").add_task(Asks(answer.clone()));
+        cli.run_batch(&(), "deploy").unwrap();
+        assert_eq!(answer.get(), Some(false));
+        let cli = cli.auto_confirm(Some(true));
+        cli.run_batch(&(), "deploy").unwrap();
+        assert_eq!(answer.get(), Some(true));
+    }
+
+    #[test]
+    fn an_idle_prompt_does_what_it_was_told() {
+        let idle = |action| Cli::new("test").idle_timeout(Duration::from_secs(1), action);
+        let cli = idle(IdleAction::RunCommand("logout".into()));
+        assert!(matches!(cli.idle_input(), Input::Line(l) if l == "logout"));
+        let cli = idle(IdleAction::Exit);
+        assert!(matches!(
+            cli.idle_input(),
+            Input::Action(TaskAction::Exit(0))
+        ));
+        let cli = idle(IdleAction::Callback(Box::new(|| TaskAction::Exit(3))));
+        assert!(matches!(
+            cli.idle_input(),
+            Input::Action(TaskAction::Exit(3))
+        ));
+        assert!(matches!(Cli::new("test").idle_input(), Input::Skip));
+    }
+
+    #[test]
+    fn secret_values_are_masked() {
+        let login = Command::new("login")
+            .arg(Arg::new("password").short('p').long("password"))
+            .arg(Arg::new("token"))
+            .arg(Arg::new("user").long("user"));
+        let cli = Cli::new("test")
+            .add_task(Named(login))
+            .add_task(Named(Command::new("tick")))
+            .mask_arg("login", "password")
+            .mask_arg("login", "token");
+        for (line, written) in [
+            ("login -p hunter2 --user bob", "login -p **** --user bob"),
+            ("login --password=hunter2 abc", "login --password=**** ****"),
+            (
+                "tick && login --user 'b o' x",
+                "tick && login --user 'b o' ****",
+            ),
+            ("tick 'a  b'", "tick 'a  b'"),
+        ] {
+            assert_eq!(cli.masked(line), written);
+        }
     }
 }
@@ -0,0 +1,74 @@
+use crate::theme;
+use promkit::crossterm::{
+    cursor, queue,
+    style::{Color, Print},
+    terminal::{self, Clear, ClearType},
+};
+use std::io::Write;
+
+// arrange `items` in columns for a terminal `width` wide, keeping at most `max_rows` rows
+pub(crate) fn layout(items: &[String], width: usize, max_rows: usize) -> Vec<String> {
+    if items.is_empty() || max_rows == 0 {
+        return vec![];
+    }
+    let col_width = items.iter().map(|s| s.chars().count()).max().unwrap_or(0) + 2;
+    let cols = (width / col_width).max(1);
+    let mut rows = items
+        .chunks(cols)
+        .map(|r| {
+            r.iter()
+                .map(|s| format!("{:<w$}", s, w = col_width))
+                .collect::<String>()
+                .trim_end()
+                .to_string()
+        })
+        .collect::<Vec<_>>();
+    if rows.len() > max_rows {
+        rows.truncate(max_rows - 1);
+        let shown = rows.len() * cols;
+        rows.push(format!("…and {} more", items.len() - shown));
+    }
+    rows
+}
+
+pub(crate) fn show<W: Write>(
+    out: &mut W,
+    items: &[String],
+    max_rows: usize,
+    color: Color,
+) -> std::io::Result<()> {
+    let rows = layout(items, terminal::size()?.0 as usize, max_rows);
+    if rows.is_empty() {
+        return Ok(());
+    }
+    // make room first so a prompt on the last line scrolls up instead of being overwritten
+    let (col, _) = cursor::position()?;
+    for _ in 0..rows.len() {
+        queue!(out, Print("\r\n"))?;
+    }
+    out.flush()?;
+    let row = cursor::position()?.1.saturating_sub(rows.len() as u16);
+    for (i, r) in rows.iter().enumerate() {
+        queue!(
+            out,
+            cursor::MoveTo(0, row + 1 + i as u16),
+            Clear(ClearType::CurrentLine),
+            Print(theme::paint(r, color))
+        )?;
+    }
+    queue!(out, cursor::MoveTo(col, row))?;
+    out.flush()
+}
+
+pub(crate) fn clear<W: Write>(out: &mut W) -> std::io::Result<()> {
+    let (col, row) = cursor::position()?;
+    if row + 1 < terminal::size()?.1 {
+        queue!(
+            out,
+            cursor::MoveTo(0, row + 1),
+            Clear(ClearType::FromCursorDown),
+            cursor::MoveTo(col, row)
+        )?;
+    }
+    out.flush()
+}
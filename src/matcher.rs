@@ -0,0 +1,158 @@
+use crate::fuzzy;
+use std::cell::Cell;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompletionMode {
+    #[default]
+    Prefix,
+    Fuzzy,
+    Substring,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CompletionOrder {
+    #[default]
+    Insertion,
+    Alphabetical,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct CompleteOptions {
+    pub max_candidates: Option<usize>,
+    pub order: CompletionOrder,
+}
+
+impl CompleteOptions {
+    pub fn max_candidates(mut self, n: usize) -> Self {
+        self.max_candidates = Some(n);
+        self
+    }
+
+    pub fn order(mut self, order: CompletionOrder) -> Self {
+        self.order = order;
+        self
+    }
+}
+
+// how candidates are matched against the typed text; set by the Cli for the
+// duration of a completion so that `complete` behaves the same inside tasks
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub(crate) struct Matcher {
+    pub(crate) case_insensitive: bool,
+    pub(crate) mode: CompletionMode,
+    pub(crate) options: CompleteOptions,
+}
+
+thread_local! {
+    static CURRENT: Cell<Matcher> = Cell::new(Matcher::default());
+}
+
+impl Matcher {
+    pub(crate) fn current() -> Self {
+        CURRENT.with(|c| c.get())
+    }
+
+    pub(crate) fn scope<R>(self, f: impl FnOnce() -> R) -> R {
+        let prev = CURRENT.with(|c| c.replace(self));
+        let r = f();
+        CURRENT.with(|c| c.set(prev));
+        r
+    }
+
+    fn score(&self, candidate: &str, text: &str) -> Option<i64> {
+        let (candidate, text) = if self.case_insensitive {
+            (candidate.to_lowercase(), text.to_lowercase())
+        } else {
+            (candidate.to_string(), text.to_string())
+        };
+        match self.mode {
+            CompletionMode::Prefix => candidate.starts_with(&text).then_some(0),
+            CompletionMode::Substring => candidate.find(&text).map(|i| -(i as i64)),
+            CompletionMode::Fuzzy => fuzzy::score(&candidate, &text),
+        }
+    }
+
+    // every matching candidate, best first; equally good ones keep the configured order,
+    // except that fuzzy and substring matches always break ties alphabetically
+    pub(crate) fn sorted(&self, candidates: Vec<String>, text: &str) -> Vec<String> {
+        let mut scored = candidates
+            .into_iter()
+            .filter_map(|c| self.score(&c, text).map(|s| (s, c)))
+            .collect::<Vec<_>>();
+        let alphabetical = self.options.order == CompletionOrder::Alphabetical
+            || self.mode != CompletionMode::Prefix;
+        match alphabetical {
+            false => scored.sort_by(|(a, _), (b, _)| b.cmp(a)),
+            true => scored.sort_by(|(a, x), (b, y)| b.cmp(a).then_with(|| x.cmp(y))),
+        }
+        scored.into_iter().map(|(_, c)| c).collect()
+    }
+
+    // the candidates worth offering, capped at the configured maximum
+    pub(crate) fn rank(&self, candidates: Vec<String>, text: &str) -> Vec<String> {
+        let mut ranked = self.sorted(candidates, text);
+        if let Some(n) = self.options.max_candidates {
+            ranked.truncate(n);
+        }
+        ranked
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names(names: &[&str]) -> Vec<String> {
+        names.iter().map(|n| n.to_string()).collect()
+    }
+
+    #[test]
+    fn prefix_keeps_insertion_order() {
+        let m = Matcher::default();
+        assert_eq!(
+            m.sorted(names(&["stop", "start", "status"]), "st"),
+            names(&["stop", "start", "status"])
+        );
+    }
+
+    #[test]
+    fn equal_scores_sort_alphabetically() {
+        for mode in [CompletionMode::Fuzzy, CompletionMode::Substring] {
+            let m = Matcher {
+                mode,
+                ..Default::default()
+            };
+            // all three match at the start and are as long, equally well
+            assert_eq!(
+                m.sorted(names(&["stop", "stem", "stab"]), "st"),
+                names(&["stab", "stem", "stop"]),
+                "{:?}",
+                mode
+            );
+        }
+    }
+
+    #[test]
+    fn alphabetical_order_for_prefix() {
+        let m = Matcher {
+            options: CompleteOptions::default().order(CompletionOrder::Alphabetical),
+            ..Default::default()
+        };
+        assert_eq!(
+            m.sorted(names(&["stop", "start", "status"]), "st"),
+            names(&["start", "status", "stop"])
+        );
+    }
+
+    #[test]
+    fn rank_caps_the_candidates() {
+        let m = Matcher {
+            options: CompleteOptions::default().max_candidates(2),
+            ..Default::default()
+        };
+        assert_eq!(
+            m.rank(names(&["a1", "a2", "a3"]), "a"),
+            names(&["a1", "a2"])
+        );
+    }
+}
@@ -0,0 +1,124 @@
+use crate::CliError;
+use promkit::{
+    crossterm::{
+        cursor,
+        event::{self, Event, KeyCode, KeyEvent, KeyModifiers},
+        execute, queue,
+        style::{Attribute, Print, SetAttribute},
+        terminal::{self, Clear, ClearType, EnterAlternateScreen, LeaveAlternateScreen},
+    },
+    grapheme::Graphemes,
+};
+use std::io::{self, IsTerminal, Write};
+
+// `text` a screen at a time when it would not fit the terminal, printed as is otherwise or
+// when there is no terminal to page on
+pub fn page(text: &str) -> Result<(), CliError> {
+    let lines = text.lines().collect::<Vec<_>>();
+    let rows = match terminal::size() {
+        Ok((_, rows)) if io::stdin().is_terminal() && io::stdout().is_terminal() => rows,
+        _ => 0,
+    };
+    let mut out = io::stdout();
+    if rows < 2 || lines.len() < rows as usize {
+        write!(out, "{}", text)?;
+        if !text.ends_with('\n') {
+            writeln!(out)?;
+        }
+        out.flush()?;
+        return Ok(());
+    }
+    execute!(out, EnterAlternateScreen, cursor::Hide)?;
+    terminal::enable_raw_mode()?;
+    let r = browse(&mut out, &lines);
+    terminal::disable_raw_mode()?;
+    execute!(out, cursor::Show, LeaveAlternateScreen)?;
+    r
+}
+
+// cut to the terminal's width, a wrapped line would push the others off; color escapes
+// take no room, and the ones past the cut are kept for what they reset
+fn fit(line: &str, cols: usize) -> String {
+    let mut fitted = String::new();
+    let (mut width, mut full) = (0, false);
+    let mut rest = line;
+    while !rest.is_empty() {
+        if let Some(esc) = rest.strip_prefix("\x1b[") {
+            let len = esc
+                .find(|c: char| ('@'..='~').contains(&c))
+                .map_or(esc.len(), |i| i + 1);
+            fitted.push_str(&rest[..2 + len]);
+            rest = &esc[len..];
+            continue;
+        }
+        let end = rest[1..].find('\x1b').map_or(rest.len(), |i| i + 1);
+        for g in Graphemes::from(&rest[..end]).iter() {
+            full = full || width + g.width > cols;
+            if !full {
+                fitted.push(g.ch);
+                width += g.width;
+            }
+        }
+        rest = &rest[end..];
+    }
+    fitted
+}
+
+// space or PageDown for the next page, Enter or Down for the next line, b or PageUp and
+// Up back again, q, Esc or Ctrl+C to leave
+fn browse(out: &mut io::Stdout, lines: &[&str]) -> Result<(), CliError> {
+    let mut top = 0;
+    loop {
+        let (cols, rows) = terminal::size()?;
+        let height = (rows as usize).saturating_sub(1).max(1);
+        let last = lines.len().saturating_sub(height);
+        top = top.min(last);
+        queue!(out, cursor::MoveTo(0, 0), Clear(ClearType::All))?;
+        for line in &lines[top..(top + height).min(lines.len())] {
+            queue!(out, Print(fit(line, cols as usize)), Print("\r\n"))?;
+        }
+        let status = match top == last {
+            true => "(END) q to leave".to_string(),
+            false => format!(
+                "-- {}% -- space: page, enter: line, b: back, q: leave",
+                (top + height) * 100 / lines.len()
+            ),
+        };
+        queue!(
+            out,
+            cursor::MoveTo(0, rows.saturating_sub(1)),
+            SetAttribute(Attribute::Reverse),
+            Print(fit(&status, cols as usize)),
+            SetAttribute(Attribute::Reset)
+        )?;
+        out.flush()?;
+        let (code, modifiers) = match crate::keys::normalize(event::read()?) {
+            Event::Key(KeyEvent { code, modifiers }) => (code, modifiers),
+            // drawn again for the new size
+            _ => continue,
+        };
+        match code {
+            KeyCode::Char('q') | KeyCode::Esc => return Ok(()),
+            KeyCode::Char('c') if modifiers == KeyModifiers::CONTROL => return Ok(()),
+            KeyCode::Char(' ') | KeyCode::PageDown => top += height,
+            KeyCode::Enter | KeyCode::Down | KeyCode::Char('j') => top += 1,
+            KeyCode::Char('b') | KeyCode::PageUp => top = top.saturating_sub(height),
+            KeyCode::Up | KeyCode::Char('k') => top = top.saturating_sub(1),
+            KeyCode::Char('g') | KeyCode::Home => top = 0,
+            KeyCode::Char('G') | KeyCode::End => top = last,
+            _ => {}
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escapes_take_no_room() {
+        assert_eq!(fit("abcdef", 4), "abcd");
+        assert_eq!(fit("\x1b[1mUsage:\x1b[0m run", 3), "\x1b[1mUsa\x1b[0m");
+        assert_eq!(fit("\x1b[1mab\x1b[0m", 2), "\x1b[1mab\x1b[0m");
+    }
+}
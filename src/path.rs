@@ -0,0 +1,42 @@
+use std::path::PathBuf;
+
+pub(crate) fn expand_home(dir: &str) -> PathBuf {
+    match dir.strip_prefix('~') {
+        Some(rest) if rest.is_empty() || rest.starts_with('/') => {
+            let mut p = PathBuf::from(std::env::var("HOME").unwrap_or_default());
+            p.push(rest.trim_start_matches('/'));
+            p
+        }
+        _ if dir.is_empty() => PathBuf::from("."),
+        _ => PathBuf::from(dir),
+    }
+}
+
+// complete `token` against the filesystem, keeping the typed directory part as is
+pub(crate) fn path_candidates(token: &str, dirs_only: bool) -> Vec<String> {
+    let token = if token == "~" { "~/" } else { token };
+    let (dir, prefix) = match token.rfind('/') {
+        Some(i) => token.split_at(i + 1),
+        None => ("", token),
+    };
+    let entries = match std::fs::read_dir(expand_home(dir)) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut candidates = entries
+        .filter_map(|e| e.ok())
+        .filter_map(|e| {
+            let name = e.file_name().into_string().ok()?;
+            if !name.starts_with(prefix) || (name.starts_with('.') && !prefix.starts_with('.')) {
+                return None;
+            }
+            let is_dir = std::fs::metadata(e.path()).is_ok_and(|m| m.is_dir());
+            if dirs_only && !is_dir {
+                return None;
+            }
+            Some(format!("{}{}{}", dir, name, if is_dir { "/" } else { "" }))
+        })
+        .collect::<Vec<_>>();
+    candidates.sort();
+    candidates
+}
@@ -0,0 +1,153 @@
+use promkit::{
+    crossterm::{
+        cursor::{MoveToColumn, MoveUp},
+        queue,
+        style::{Color, Print, SetForegroundColor},
+        terminal::{self, Clear, ClearType},
+    },
+    grapheme::Graphemes,
+    readline::State,
+};
+use std::{
+    fmt::Display,
+    io::{self, Write},
+    sync::{Arc, Mutex, MutexGuard},
+};
+
+// the prompt as it is on the screen, to be drawn again below a message
+pub(crate) struct Drawn {
+    label: String,
+    label_width: usize,
+    color: Color,
+    line: Graphemes,
+    // the cursor's column, counted from where the label starts
+    at: usize,
+}
+
+impl Drawn {
+    pub(crate) fn of(state: &State) -> Drawn {
+        let editor = &state.0.editor;
+        let line: Graphemes = match &state.1.mask {
+            Some(mask) => editor.data.iter().map(|_| mask.clone()).collect(),
+            None => editor.data.clone(),
+        };
+        let label_width = state.1.label.width();
+        Drawn {
+            label: state.1.label.to_string(),
+            label_width,
+            color: state.1.label_color,
+            at: label_width
+                + line[..editor.position()]
+                    .iter()
+                    .map(|g| g.width)
+                    .sum::<usize>(),
+            line,
+        }
+    }
+}
+
+// prints from any thread while the prompt is up, which comes back below the message;
+// without a prompt it is a plain `println!`
+#[derive(Clone, Default)]
+pub struct PromptPrinter {
+    shown: Arc<Mutex<Option<Drawn>>>,
+}
+
+impl PromptPrinter {
+    // held while the prompt draws, so a message never lands in the middle of it
+    pub(crate) fn lock(&self) -> MutexGuard<'_, Option<Drawn>> {
+        self.shown.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub fn println<D: Display>(&self, msg: D) {
+        let shown = self.lock();
+        let cols = terminal::size().map_or(80, |(cols, _)| cols as usize);
+        // nowhere left to tell if stdout is gone
+        let _ = write_above(&mut io::stdout(), shown.as_ref(), &msg.to_string(), cols);
+    }
+}
+
+pub(crate) fn write_above<W: Write>(
+    out: &mut W,
+    shown: Option<&Drawn>,
+    msg: &str,
+    cols: usize,
+) -> io::Result<()> {
+    let drawn = match shown {
+        Some(drawn) => drawn,
+        None => {
+            writeln!(out, "{}", msg)?;
+            return out.flush();
+        }
+    };
+    let cols = cols.max(1);
+    // up to the row the label is on; this crossterm moves one row for `MoveUp(0)`
+    if drawn.at / cols > 0 {
+        queue!(out, MoveUp((drawn.at / cols) as u16))?;
+    }
+    queue!(out, Print('\r'), Clear(ClearType::FromCursorDown))?;
+    // the terminal is in raw mode, every line needs its carriage return
+    for line in msg.lines() {
+        queue!(out, Print(line), Print("\r\n"))?;
+    }
+    queue!(
+        out,
+        SetForegroundColor(drawn.color),
+        Print(&drawn.label),
+        SetForegroundColor(Color::Reset),
+        Print(drawn.line.to_string())
+    )?;
+    // from the end of the line back to the cursor
+    let up = (drawn.label_width + drawn.line.width()) / cols - drawn.at / cols;
+    if up > 0 {
+        queue!(out, MoveUp(up as u16))?;
+    }
+    queue!(out, MoveToColumn((drawn.at % cols) as u16 + 1))?;
+    out.flush()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn drawn(label: &str, line: &str, at: usize) -> Drawn {
+        Drawn {
+            label: label.to_string(),
+            label_width: Graphemes::from(label).width(),
+            color: Color::Reset,
+            line: Graphemes::from(line),
+            at,
+        }
+    }
+
+    fn written(shown: Option<&Drawn>, msg: &str, cols: usize) -> String {
+        let mut out = Vec::new();
+        write_above(&mut out, shown, msg, cols).unwrap();
+        String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn plain_without_a_prompt() {
+        assert_eq!(written(None, "done", 80), "done\n");
+    }
+
+    #[test]
+    fn prompt_comes_back_below() {
+        let out = written(Some(&drawn("> ", "stat", 3)), "one\ntwo", 80);
+        let (before, after) = out.split_once("one\r\ntwo\r\n").unwrap();
+        // the prompt's row cleared first, and drawn again after
+        assert_eq!(before, "\r\x1b[J");
+        assert!(after.contains("> "), "{:?}", after);
+        assert!(after.contains("stat"), "{:?}", after);
+        // back to the cursor, one past the `s`
+        assert!(after.ends_with("\x1b[4G"), "{:?}", after);
+    }
+
+    #[test]
+    fn wrapped_prompt_is_cleared_from_its_first_row() {
+        // `> abcdef` on 4 columns takes two rows and the cursor is at its end
+        let out = written(Some(&drawn("> ", "abcdef", 8)), "msg", 4);
+        assert!(out.starts_with("\x1b[2A\r\x1b[J"), "{:?}", out);
+        assert!(out.ends_with("\x1b[1G"), "{:?}", out);
+    }
+}
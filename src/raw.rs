@@ -0,0 +1,49 @@
+use crate::{Task, TaskAction};
+use clap::{Arg, ArgMatches, Command};
+use promkit::grapheme::Graphemes;
+use std::rc::Rc;
+
+// a command that parses the rest of its line itself
+pub trait RawTask {
+    fn name(&self) -> &str;
+    fn about(&self) -> Option<&str> {
+        None
+    }
+    fn action(&self, rest: &str) -> TaskAction;
+    // the completed rest of the line
+    fn suggests(&self, rest: &str) -> Option<Graphemes> {
+        let _ = rest;
+        None
+    }
+}
+
+// stands in for a raw task wherever the Cli wants a Command, e.g. in help and completion
+pub(crate) struct Raw(pub(crate) Rc<dyn RawTask>);
+
+impl<C> Task<C> for Raw {
+    fn command(&self) -> Command {
+        let cmd = Command::new(self.0.name().to_string()).arg(
+            Arg::new("rest")
+                .num_args(0..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true),
+        );
+        match self.0.about() {
+            Some(about) => cmd.about(about.to_string()),
+            None => cmd,
+        }
+    }
+    fn action(&self, _: &C, matches: &ArgMatches) -> TaskAction {
+        let rest = matches
+            .get_many::<String>("rest")
+            .map(|r| r.cloned().collect::<Vec<_>>().join(" "))
+            .unwrap_or_default();
+        self.0.action(&rest)
+    }
+    fn suggests(&self, args: &[&'_ str]) -> Option<Graphemes> {
+        self.0.suggests(&args.join(" "))
+    }
+    fn candidates(&self, _: &[&'_ str]) -> Vec<Graphemes> {
+        vec![]
+    }
+}
@@ -0,0 +1,49 @@
+use promkit::{
+    crossterm::{
+        cursor::{MoveToColumn, RestorePosition, SavePosition},
+        queue,
+        style::Print,
+        terminal::{self, Clear, ClearType},
+    },
+    grapheme::Graphemes,
+    readline::State,
+};
+use std::{
+    io::{self, Write},
+    sync::Arc,
+};
+
+pub(crate) type RightLabel = Arc<dyn Fn() -> String + Send + Sync>;
+
+// `text` at the right edge of the prompt's row, or nothing once the line (and `after`
+// drawn behind it) would run into it, like zsh's RPROMPT
+pub(crate) fn draw(
+    out: &mut io::Stdout,
+    state: &State,
+    text: &str,
+    after: usize,
+) -> promkit::Result<()> {
+    let cols = terminal::size()?.0 as usize;
+    let used = state.1.label.width() + state.0.editor.data.width() + after;
+    // a wrapped line has the cursor on another row, the render cleared the old one anyway
+    if used >= cols {
+        return Ok(());
+    }
+    let text = Graphemes::from(text.lines().next().unwrap_or_default());
+    queue!(
+        out,
+        SavePosition,
+        // this crossterm counts columns from 1 here
+        MoveToColumn(used as u16 + 1),
+        Clear(ClearType::UntilNewLine)
+    )?;
+    if !text.is_empty() && used + 1 + text.width() <= cols {
+        queue!(
+            out,
+            MoveToColumn((cols - text.width()) as u16 + 1),
+            Print(text.to_string())
+        )?;
+    }
+    queue!(out, RestorePosition)?;
+    out.flush()
+}
@@ -0,0 +1,317 @@
+use crate::{lex, CliError, TaskAction};
+use std::time::Duration;
+
+// the logical lines of a script with the number of the line each starts on;
+// comments are stripped and lines ending in `\` are joined with the next one, and so are
+// lines ending in an open quote when `quotes` is on
+pub(crate) struct Lines<I> {
+    lines: I,
+    number: usize,
+    quotes: bool,
+}
+
+impl<I> Lines<I> {
+    pub(crate) fn new(lines: I, quotes: bool) -> Self {
+        Lines {
+            lines,
+            number: 0,
+            quotes,
+        }
+    }
+}
+
+impl<I: Iterator<Item = Result<S, CliError>>, S: AsRef<str>> Iterator for Lines<I> {
+    type Item = Result<(usize, String), CliError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        let mut joined: Option<String> = None;
+        let mut start = None;
+        for line in self.lines.by_ref() {
+            let line = match line {
+                Ok(line) => line,
+                Err(e) => return Some(Err(e)),
+            };
+            // a script written on Windows
+            let line = line.as_ref();
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            self.number += 1;
+            let start = *start.get_or_insert(self.number);
+            let joined = match joined.as_mut() {
+                // no comment can start inside the quote
+                Some(j) => {
+                    let ends = lex::ends(j);
+                    let line = match ends {
+                        lex::Ends::Quote => line,
+                        _ => lex::strip_comment(line),
+                    };
+                    lex::join(j, ends, line);
+                    j
+                }
+                None => joined.insert(lex::strip_comment(line).to_string()),
+            };
+            // a quote left open otherwise fails on its own line
+            let goes_on = match lex::ends(joined) {
+                lex::Ends::Done => false,
+                lex::Ends::Backslash => true,
+                lex::Ends::Quote => self.quotes,
+            };
+            if !goes_on {
+                return Some(Ok((start, std::mem::take(joined))));
+            }
+        }
+        let message = match joined.as_deref().map(lex::ends) {
+            Some(lex::Ends::Quote) => "unterminated quote",
+            _ => "unfinished line continuation",
+        };
+        start.map(|line| {
+            Err(CliError::Script {
+                line,
+                message: message.into(),
+            })
+        })
+    }
+}
+
+// every non-empty command of a script, with the number of the line it is on; what
+// `whole` claims is not split any further
+pub(crate) fn commands<I, S, F>(
+    lines: I,
+    quotes: bool,
+    whole: F,
+) -> impl Iterator<Item = Result<(usize, String), CliError>>
+where
+    I: Iterator<Item = Result<S, CliError>>,
+    S: AsRef<str>,
+    F: Fn(&str) -> bool,
+{
+    Lines::new(lines, quotes).flat_map(move |line| match line {
+        Ok((n, line)) => lex::split_unquoted(&line, ';', &whole)
+            .into_iter()
+            .map(|s| s.trim())
+            .filter(|s| !s.is_empty())
+            .map(|s| Ok((n, s.to_string())))
+            .collect::<Vec<_>>(),
+        Err(e) => vec![Err(e)],
+    })
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ErrorPolicy {
+    // stop at the first failing command
+    #[default]
+    Abort,
+    // print the error and go on with the next command
+    Continue,
+    // go on silently, the errors are only reported at the end
+    Collect,
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct BatchOptions {
+    pub on_error: ErrorPolicy,
+    // print every command before it runs
+    pub echo: bool,
+    // measure every command into `BatchReport::lines`
+    pub timed: bool,
+    // print the measured commands, slowest first, once the script is done
+    pub timing_summary: bool,
+    // a quote left open at the end of a line goes on on the next one, like at the prompt
+    pub join_quotes: bool,
+}
+
+impl BatchOptions {
+    pub fn on_error(mut self, policy: ErrorPolicy) -> Self {
+        self.on_error = policy;
+        self
+    }
+
+    pub fn echo(mut self, on: bool) -> Self {
+        self.echo = on;
+        self
+    }
+
+    pub fn timed(mut self, on: bool) -> Self {
+        self.timed = on;
+        self
+    }
+
+    pub fn join_quotes(mut self, on: bool) -> Self {
+        self.join_quotes = on;
+        self
+    }
+
+    // implies `timed`
+    pub fn timing_summary(mut self, on: bool) -> Self {
+        self.timing_summary = on;
+        self.timed |= on;
+        self
+    }
+}
+
+#[derive(Debug)]
+pub struct BatchReport {
+    // what the last command asked for, `Continue` if the script ran to its end
+    pub action: TaskAction,
+    // line number, command and error of every command that failed
+    pub errors: Vec<(usize, String, CliError)>,
+    // every command that ran, only filled in when timed
+    pub lines: Vec<BatchLineReport>,
+}
+
+impl Default for BatchReport {
+    fn default() -> Self {
+        BatchReport {
+            action: TaskAction::Continue,
+            errors: vec![],
+            lines: vec![],
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct BatchLineReport {
+    pub line_no: usize,
+    pub text: String,
+    pub duration: Duration,
+    // the error is kept as its message
+    pub result: Result<TaskAction, String>,
+}
+
+// a row per command, slowest first
+fn summary(lines: &[BatchLineReport]) -> Vec<String> {
+    let mut lines = lines.iter().collect::<Vec<_>>();
+    lines.sort_by_key(|l| std::cmp::Reverse(l.duration));
+    lines
+        .into_iter()
+        .map(|l| {
+            let status = match l.result {
+                Ok(_) => "ok",
+                Err(_) => "failed",
+            };
+            format!(
+                "{:>12} {:>6}  line {}: {}",
+                format!("{:.3?}", l.duration),
+                status,
+                l.line_no,
+                l.text
+            )
+        })
+        .collect()
+}
+
+pub(crate) fn print_summary(lines: &[BatchLineReport]) {
+    for row in summary(lines) {
+        println!("{}", row);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn lines(script: &str, quotes: bool) -> Vec<Result<(usize, String), String>> {
+        Lines::new(script.split('\n').map(Ok::<_, CliError>), quotes)
+            .map(|l| l.map_err(|e| e.to_string()))
+            .collect()
+    }
+
+    #[test]
+    fn backslash_joins_lines() {
+        assert_eq!(
+            lines("echo a \\\nb\necho c", false),
+            vec![Ok((1, "echo a  b".into())), Ok((3, "echo c".into()))]
+        );
+    }
+
+    #[test]
+    fn crlf_endings_are_dropped() {
+        assert_eq!(
+            lines("echo a\r\necho 'b\r\nc' \\\r\nd", true),
+            vec![Ok((1, "echo a".into())), Ok((2, "echo 'b\nc'  d".into()))]
+        );
+    }
+
+    #[test]
+    fn open_quote_stays_on_its_line() {
+        assert_eq!(
+            lines("echo 'a\necho b", false),
+            vec![Ok((1, "echo 'a".into())), Ok((2, "echo b".into()))]
+        );
+    }
+
+    #[test]
+    fn open_quote_joins_when_asked() {
+        assert_eq!(
+            lines("echo 'a\nb'\necho c", true),
+            vec![Ok((1, "echo 'a\nb'".into())), Ok((3, "echo c".into()))]
+        );
+        assert_eq!(
+            lines("echo 'a\necho b", true),
+            vec![Err("error: line 1: unterminated quote".into())]
+        );
+    }
+
+    #[test]
+    fn a_commented_backslash_does_not_join() {
+        assert_eq!(
+            lines("echo a # \\\necho b", false),
+            vec![Ok((1, "echo a ".into())), Ok((2, "echo b".into()))]
+        );
+    }
+
+    #[test]
+    fn a_backslash_on_the_last_line_is_an_error() {
+        assert_eq!(
+            lines("echo a\necho b \\", false),
+            vec![
+                Ok((1, "echo a".into())),
+                Err("error: line 2: unfinished line continuation".into())
+            ]
+        );
+    }
+
+    #[test]
+    fn commands_split_at_semicolons_and_skip_empty_ones() {
+        let commands = commands(
+            "a; b\n\n;c # d".split('\n').map(Ok::<_, CliError>),
+            false,
+            |_| false,
+        )
+        .map(|c| c.unwrap())
+        .collect::<Vec<_>>();
+        assert_eq!(
+            commands,
+            [(1, "a".into()), (1, "b".into()), (3, "c".to_string())]
+        );
+    }
+
+    fn ran(line_no: usize, ms: u64, result: Result<TaskAction, String>) -> BatchLineReport {
+        BatchLineReport {
+            line_no,
+            text: format!("cmd {}", line_no),
+            duration: Duration::from_millis(ms),
+            result,
+        }
+    }
+
+    #[test]
+    fn the_summary_puts_the_slowest_first() {
+        let rows = summary(&[
+            ran(1, 2, Ok(TaskAction::Continue)),
+            ran(2, 30, Err("boom".into())),
+        ]);
+        assert_eq!(
+            rows,
+            [
+                "    30.000ms failed  line 2: cmd 2",
+                "     2.000ms     ok  line 1: cmd 1"
+            ]
+        );
+    }
+
+    #[test]
+    fn a_summary_is_always_timed() {
+        assert!(BatchOptions::default().timing_summary(true).timed);
+        assert!(!BatchOptions::default().timed);
+    }
+}
@@ -0,0 +1,198 @@
+use promkit::{
+    buffer::Buffer,
+    crossterm::{
+        queue,
+        style::{Print, SetForegroundColor},
+        terminal::{Clear, ClearType},
+    },
+    grapheme::Graphemes,
+    readline::State,
+    state::Render,
+    termutil,
+};
+use std::io::{self, Write};
+
+// what Enter does with the line found by a reverse search
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum SearchAccept {
+    // put it into the editor to be looked at first
+    Edit,
+    // run it right away
+    Run,
+}
+
+// a reverse incremental search through the history, drawn over the prompt line
+pub(crate) struct Search {
+    query: String,
+    original: Buffer,
+    // the history entry matched, counted from the oldest
+    found: Option<usize>,
+    failed: bool,
+    // how far the cursor went while drawing, to get back to where the prompt starts
+    drawn: usize,
+}
+
+fn entries(state: &State) -> &[Graphemes] {
+    state.1.hstr.as_ref().map_or(&[], |h| &h.data)
+}
+
+impl Search {
+    pub(crate) fn start(out: &mut io::Stdout, state: &State) -> promkit::Result<Search> {
+        let mut search = Search {
+            query: String::new(),
+            original: (*state.0.editor).clone(),
+            found: None,
+            failed: false,
+            drawn: state.1.label.width() + state.0.prev.width_to_position(),
+        };
+        search.draw(out, state)?;
+        Ok(search)
+    }
+
+    // the newest entry before `before` holding the query
+    fn find(&mut self, entries: &[Graphemes], before: usize) {
+        let found = entries[..before]
+            .iter()
+            .rposition(|h| !h.is_empty() && h.to_string().contains(&self.query));
+        self.failed = found.is_none();
+        if found.is_some() {
+            self.found = found;
+        }
+    }
+
+    pub(crate) fn push(
+        &mut self,
+        out: &mut io::Stdout,
+        state: &State,
+        ch: char,
+    ) -> promkit::Result<()> {
+        self.query.push(ch);
+        // the current match may still do
+        let before = self.found.map_or(entries(state).len(), |i| i + 1);
+        self.find(entries(state), before);
+        self.draw(out, state)
+    }
+
+    pub(crate) fn pop(&mut self, out: &mut io::Stdout, state: &State) -> promkit::Result<()> {
+        self.query.pop();
+        self.found = None;
+        if !self.query.is_empty() {
+            self.find(entries(state), entries(state).len());
+        } else {
+            self.failed = false;
+        }
+        self.draw(out, state)
+    }
+
+    // the next older match, on a repeated Ctrl+R
+    pub(crate) fn older(&mut self, out: &mut io::Stdout, state: &State) -> promkit::Result<()> {
+        if !self.query.is_empty() {
+            let before = self.found.unwrap_or(entries(state).len());
+            self.find(entries(state), before);
+        }
+        self.draw(out, state)
+    }
+
+    fn matched(&self, state: &State) -> Option<Graphemes> {
+        entries(state).get(self.found?).cloned()
+    }
+
+    fn draw(&mut self, out: &mut io::Stdout, state: &State) -> promkit::Result<()> {
+        termutil::move_left(out, self.drawn as u16)?;
+        let text = format!(
+            "({}reverse-i-search)'{}': {}",
+            if self.failed { "failed " } else { "" },
+            self.query,
+            self.matched(state)
+                .map(|m| m.to_string())
+                .unwrap_or_default()
+        );
+        queue!(out, Clear(ClearType::FromCursorDown), Print(&text))?;
+        out.flush()?;
+        self.drawn = Graphemes::from(text).width();
+        Ok(())
+    }
+
+    // back to the prompt with the match in the editor, or what was there before if `keep` is off
+    pub(crate) fn finish(
+        self,
+        out: &mut io::Stdout,
+        state: &mut State,
+        keep: bool,
+    ) -> promkit::Result<()> {
+        let line = match self.matched(state) {
+            Some(m) if keep => {
+                let b = Buffer {
+                    data: m,
+                    ..Default::default()
+                };
+                b.to_tail();
+                b
+            }
+            _ => self.original,
+        };
+        termutil::move_left(out, self.drawn as u16)?;
+        queue!(
+            out,
+            Clear(ClearType::FromCursorDown),
+            SetForegroundColor(state.1.label_color),
+            Print(state.1.label.to_string()),
+            SetForegroundColor(promkit::crossterm::style::Color::Reset),
+        )?;
+        out.flush()?;
+        // drawn afresh from the label on
+        *state.0.editor = line;
+        *state.0.prev = Buffer::default();
+        state.0.next = state.0.editor.clone();
+        state.render(out)?;
+        *state.0.prev = (*state.0.editor).clone();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn search(query: &str) -> Search {
+        Search {
+            query: query.to_string(),
+            original: Buffer::default(),
+            found: None,
+            failed: false,
+            drawn: 0,
+        }
+    }
+
+    fn history() -> Vec<Graphemes> {
+        ["make test", "ls", "make", "cd src", ""]
+            .into_iter()
+            .map(Graphemes::from)
+            .collect()
+    }
+
+    #[test]
+    fn the_newest_match_comes_first() {
+        let h = history();
+        let mut s = search("ma");
+        s.find(&h, h.len());
+        assert_eq!(s.found, Some(2));
+        // a repeated Ctrl+R looks further back
+        s.find(&h, 2);
+        assert_eq!(s.found, Some(0));
+    }
+
+    #[test]
+    fn a_failed_search_keeps_the_last_match() {
+        let h = history();
+        let mut s = search("make");
+        s.find(&h, h.len());
+        s.find(&h, 2);
+        s.find(&h, 0);
+        assert!(s.failed);
+        assert_eq!(s.found, Some(0));
+        let mut s = search("");
+        s.find(&h, h.len());
+        assert_eq!(s.found, Some(3), "the line being edited is never a match");
+    }
+}
@@ -0,0 +1,39 @@
+use crate::{CliError, TaskAction};
+use std::process::Command;
+
+// the shell command of a line like `! ls -la`; the space after the `!` keeps it apart from
+// history expansion, which has `!ls` for the last line starting with `ls`
+pub(crate) fn command(line: &str) -> Option<&str> {
+    let cmd = line.trim_start().strip_prefix('!')?;
+    match cmd.starts_with(char::is_whitespace) {
+        true => Some(cmd.trim_start()).filter(|c| !c.is_empty()),
+        false => None,
+    }
+}
+
+// runs `cmd` through the platform shell on the terminal of the cli
+pub(crate) fn run(cmd: &str) -> Result<TaskAction, CliError> {
+    let status = match cfg!(windows) {
+        true => Command::new("cmd").arg("/C").arg(cmd).status()?,
+        false => Command::new("sh").arg("-c").arg(cmd).status()?,
+    };
+    Ok(match status.code() {
+        Some(0) => TaskAction::Continue,
+        Some(code) => TaskAction::Error(format!("command exited with status {}", code)),
+        None => TaskAction::Error("command was terminated by a signal".into()),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn history_designators_are_no_shell_commands() {
+        assert_eq!(command("! ls -la"), Some("ls -la"));
+        assert_eq!(command("  !  ls"), Some("ls"));
+        for line in ["!ls", "!!", "!3", "!-2", "!", "!  "] {
+            assert_eq!(command(line), None, "{}", line);
+        }
+    }
+}
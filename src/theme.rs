@@ -0,0 +1,52 @@
+use promkit::crossterm::style::{style, Color, Stylize};
+use std::fmt::Display;
+
+// the colors of everything the crate draws around the line; `Color::Reset` leaves the
+// terminal's own
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub struct PromptTheme {
+    pub label: Color,
+    pub error: Color,
+    // the candidates listed under the prompt
+    pub suggestion: Color,
+    // the history hint after the cursor, dim when `None`
+    pub hint: Option<Color>,
+    // the first word while typing, naming a command or not
+    pub command: Color,
+    pub unknown: Color,
+}
+
+impl Default for PromptTheme {
+    fn default() -> Self {
+        PromptTheme {
+            label: Color::Reset,
+            error: Color::Red,
+            suggestion: Color::Reset,
+            hint: None,
+            command: Color::Green,
+            unknown: Color::Red,
+        }
+    }
+}
+
+impl PromptTheme {
+    // no colors or attributes at all, for terminals without them
+    pub fn monochrome() -> Self {
+        PromptTheme {
+            label: Color::Reset,
+            error: Color::Reset,
+            suggestion: Color::Reset,
+            hint: Some(Color::Reset),
+            command: Color::Reset,
+            unknown: Color::Reset,
+        }
+    }
+}
+
+// `text` as is for `Color::Reset`, so nothing but the text reaches a dumb terminal
+pub(crate) fn paint<D: Display>(text: D, color: Color) -> String {
+    match color {
+        Color::Reset => text.to_string(),
+        c => style(text).with(c).to_string(),
+    }
+}
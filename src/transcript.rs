@@ -0,0 +1,58 @@
+use crate::CliError;
+use std::{
+    fs,
+    io::Write,
+    path::Path,
+    time::{SystemTime, UNIX_EPOCH},
+};
+
+// `2024-05-01T12:00:03Z` for `secs` since the epoch
+fn utc(secs: u64) -> String {
+    let (days, rest) = (secs / 86400, secs % 86400);
+    // the civil date of a day count, after Howard Hinnant's `civil_from_days`
+    let z = days as i64 + 719468;
+    let era = z.div_euclid(146097);
+    let doe = z.rem_euclid(146097);
+    let yoe = (doe - doe / 1460 + doe / 36524 - doe / 146096) / 365;
+    let doy = doe - (365 * yoe + yoe / 4 - yoe / 100);
+    let mp = (5 * doy + 2) / 153;
+    let day = doy - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = yoe + era * 400 + i64::from(month <= 2);
+    format!(
+        "{:04}-{:02}-{:02}T{:02}:{:02}:{:02}Z",
+        year,
+        month,
+        day,
+        rest / 3600,
+        rest / 60 % 60,
+        rest % 60
+    )
+}
+
+// one record: every line of `text` after the time and `lead`, which is the label for a
+// command and `! ` for an error; the lines after the first get `more` instead, so later
+// kinds of record (a task's output, say) only need a lead of their own
+pub(crate) fn append(path: &Path, lead: &str, more: &str, text: &str) -> Result<(), CliError> {
+    let now = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |d| d.as_secs());
+    let stamp = utc(now);
+    let record = text
+        .lines()
+        .enumerate()
+        .map(|(i, l)| {
+            let line = format!("[{}] {}{}", stamp, if i == 0 { lead } else { more }, l);
+            line.trim_end().to_string() + "\n"
+        })
+        .collect::<String>();
+    fs::OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .and_then(|mut f| f.write_all(record.as_bytes()).and_then(|_| f.flush()))
+        .map_err(|source| CliError::File {
+            path: path.to_path_buf(),
+            source,
+        })
+}
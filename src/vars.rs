@@ -0,0 +1,170 @@
+use crate::CliError;
+use std::{cell::RefCell, collections::HashMap, rc::Rc};
+
+// every variable as a replacement for the `$NAME` or `${NAME` reference at the end
+// of `token`, keeping the text before the `$`; `None` if no variable name is being typed
+pub(crate) fn variable_candidates(token: &str, names: Vec<String>) -> Option<Vec<String>> {
+    let i = token.rfind('$')?;
+    let (head, name) = (&token[..i], &token[i + 1..]);
+    let (braced, name) = match name.strip_prefix('{') {
+        Some(n) => (true, n),
+        None => (false, name),
+    };
+    if !name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(
+        names
+            .into_iter()
+            .map(|n| match braced {
+                true => format!("{}${{{}}}", head, n),
+                false => format!("{}${}", head, n),
+            })
+            .collect(),
+    )
+}
+
+pub(crate) type Variables = Rc<RefCell<HashMap<String, String>>>;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum UndefinedVariable {
+    // expands to nothing, like in a shell
+    #[default]
+    Empty,
+    Error,
+}
+
+pub(crate) fn is_name(s: &str) -> bool {
+    let mut chars = s.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+// substitutes `$NAME` and `${NAME}` except inside single quotes; a value always stays
+// one word and is never taken for quotes or escapes by the later split
+pub(crate) fn expand(
+    line: &str,
+    lookup: impl Fn(&str) -> Option<String>,
+    undefined: UndefinedVariable,
+) -> Result<String, CliError> {
+    let mut r = String::new();
+    let mut chars = line.chars().peekable();
+    let mut quoted = false;
+    while let Some(c) = chars.next() {
+        match c {
+            '\'' if !quoted => {
+                r.push(c);
+                for c in chars.by_ref() {
+                    r.push(c);
+                    if c == '\'' {
+                        break;
+                    }
+                }
+            }
+            '"' => {
+                quoted = !quoted;
+                r.push(c);
+            }
+            '\\' => {
+                r.push(c);
+                r.extend(chars.next());
+            }
+            '$' => {
+                let braced = chars.next_if_eq(&'{').is_some();
+                let mut name = String::new();
+                while let Some(c) = chars.next_if(|c| c.is_ascii_alphanumeric() || *c == '_') {
+                    name.push(c);
+                }
+                if braced && chars.next_if_eq(&'}').is_none() || !is_name(&name) {
+                    // not a reference after all
+                    r.push('$');
+                    if braced {
+                        r.push('{');
+                    }
+                    r.push_str(&name);
+                    continue;
+                }
+                let value = match lookup(&name) {
+                    Some(v) => v,
+                    None if undefined == UndefinedVariable::Empty => String::new(),
+                    None => return Err(CliError::UndefinedVariable(name)),
+                };
+                if quoted {
+                    for c in value.chars() {
+                        if matches!(c, '"' | '\\' | '$' | '`') {
+                            r.push('\\');
+                        }
+                        r.push(c);
+                    }
+                } else if !value.is_empty() {
+                    r.push_str(&shlex::try_quote(&value).unwrap_or_default());
+                }
+            }
+            c => r.push(c),
+        }
+    }
+    Ok(r)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn names() -> Vec<String> {
+        vec!["HOME".to_string(), "PATH".to_string()]
+    }
+
+    #[test]
+    fn a_reference_gets_every_name() {
+        assert_eq!(
+            variable_candidates("x=$H", names()).unwrap(),
+            ["x=$HOME", "x=$PATH"]
+        );
+        assert_eq!(
+            variable_candidates("${P", names()).unwrap(),
+            ["${HOME}", "${PATH}"]
+        );
+    }
+
+    #[test]
+    fn no_name_being_typed_has_no_candidates() {
+        assert_eq!(variable_candidates("home", names()), None);
+        assert_eq!(variable_candidates("$HOME/bin", names()), None);
+    }
+
+    fn lookup(name: &str) -> Option<String> {
+        match name {
+            "A" => Some("1".into()),
+            "S" => Some("two words".into()),
+            _ => None,
+        }
+    }
+
+    fn expanded(line: &str) -> Result<String, String> {
+        expand(line, lookup, UndefinedVariable::Empty).map_err(|e| e.to_string())
+    }
+
+    #[test]
+    fn references_are_replaced() {
+        assert_eq!(expanded("x $A ${A}b $Ab"), Ok("x 1 1b ".into()));
+    }
+
+    #[test]
+    fn single_quotes_and_escapes_keep_the_dollar() {
+        assert_eq!(expanded("'$A' \\$A $ ${A"), Ok("'$A' \\$A $ ${A".into()));
+    }
+
+    #[test]
+    fn a_value_stays_one_word() {
+        assert_eq!(expanded("x $S"), Ok("x 'two words'".into()));
+        assert_eq!(expanded("\"$S\""), Ok("\"two words\"".into()));
+    }
+
+    #[test]
+    fn undefined_variables_can_be_errors() {
+        assert!(expand("$NOPE", lookup, UndefinedVariable::Error).is_err());
+        assert_eq!(expanded("[$NOPE]"), Ok("[]".into()));
+    }
+}
@@ -0,0 +1,267 @@
+use crate::edit::{self, key, move_to, Motion};
+use promkit::{
+    buffer::Buffer,
+    crossterm::{
+        event::{Event, KeyCode, KeyEvent, KeyModifiers},
+        queue,
+        style::{Color, Print, SetForegroundColor},
+        terminal::{Clear, ClearType},
+    },
+    grapheme::Graphemes,
+    keybind::KeyBind,
+    readline::State,
+    state::Render,
+    termutil, EventHandleFn, Handler,
+};
+use std::{
+    collections::HashMap,
+    io::{self, Write},
+    rc::Rc,
+};
+
+#[derive(Debug, Clone, Copy, Eq, PartialEq)]
+pub enum EditMode {
+    Emacs,
+    // Esc for normal mode, `[N] ` in front of the label while there
+    Vi,
+}
+
+// what a key in normal mode came to
+pub(crate) enum Step {
+    Done,
+    Search,
+    // up to the usual bindings, like Enter or Ctrl+C
+    Pass,
+}
+
+// keeps in place, only the cursor moves
+fn cut_to(chars: Rc<str>, to: Motion) -> Box<EventHandleFn<State>> {
+    Box::new(move |_, _, _: &mut io::Stdout, state: &mut State| {
+        let editor = &mut state.0.editor;
+        let at = editor.position();
+        let to = to(&editor.data, at, &chars);
+        if to > at {
+            edit::cut(editor, at, to);
+        }
+        Ok(false)
+    })
+}
+
+// the keys of normal mode that only move the cursor
+fn motions() -> [(char, Motion); 6] {
+    [
+        ('h', |_, at, _| at.saturating_sub(1)),
+        ('l', |data, at, _| (at + 1).min(data.len())),
+        ('w', edit::word_after),
+        ('b', edit::word_start),
+        ('0', |_, _, _| 0),
+        ('$', |data, _, _| data.len()),
+    ]
+}
+
+// `d` and then `c`: `dd` clears the line, `dw` cuts up to the next word
+fn delete(editor: &mut Buffer, c: char, chars: &str) {
+    match c {
+        'd' => {
+            let len = editor.data.len();
+            edit::cut(editor, 0, len);
+        }
+        'w' => {
+            let at = editor.position();
+            let to = edit::word_after(&editor.data, at, chars);
+            edit::cut(editor, at, to);
+        }
+        _ => {}
+    }
+}
+
+pub(crate) struct Vi {
+    normal: KeyBind<State>,
+    chars: Rc<str>,
+    active: bool,
+    // `d`, waiting for what to delete
+    pending: bool,
+    // the label before the marker went in front of it
+    label: Option<Graphemes>,
+}
+
+impl Vi {
+    pub(crate) fn new(chars: &str) -> Vi {
+        let chars: Rc<str> = Rc::from(chars);
+        let mut normal = KeyBind {
+            event_mapping: HashMap::default(),
+            handle_input: None,
+            handle_resize: None,
+        };
+        let none = |c| key(KeyCode::Char(c), KeyModifiers::NONE);
+        normal.assign(
+            motions()
+                .into_iter()
+                .map(|(c, m)| (none(c), move_to(chars.clone(), m))),
+        );
+        normal.assign(vec![(
+            none('x'),
+            cut_to(chars.clone(), |data, at, _| (at + 1).min(data.len())),
+        )]);
+        Vi {
+            normal,
+            chars,
+            active: false,
+            pending: false,
+            label: None,
+        }
+    }
+
+    pub(crate) fn is_normal(&self) -> bool {
+        self.active
+    }
+
+    // the label is drawn again in front of the line as it is, the cursor where the editor has it
+    fn relabel(out: &mut io::Stdout, state: &mut State, old: usize) -> promkit::Result<()> {
+        termutil::move_left(out, (old + state.0.editor.width_to_position()) as u16)?;
+        queue!(
+            out,
+            Clear(ClearType::FromCursorDown),
+            SetForegroundColor(state.1.label_color),
+            Print(state.1.label.to_string()),
+            SetForegroundColor(Color::Reset),
+        )?;
+        out.flush()?;
+        *state.0.prev = Buffer::default();
+        state.0.next = state.0.editor.clone();
+        state.render(out)?;
+        *state.0.prev = (*state.0.editor).clone();
+        Ok(())
+    }
+
+    pub(crate) fn enter_normal(
+        &mut self,
+        out: &mut io::Stdout,
+        state: &mut State,
+    ) -> promkit::Result<()> {
+        if self.active {
+            return Ok(());
+        }
+        self.active = true;
+        let old = state.1.label.width();
+        let label = std::mem::take(&mut state.1.label);
+        state.1.label = Graphemes::from(format!("[N] {}", label));
+        self.label = Some(label);
+        // like vi, the cursor steps back off the end
+        let width = state.0.editor.width_in_position();
+        if state.0.editor.prev() {
+            termutil::move_left(out, width as u16)?;
+        }
+        Self::relabel(out, state, old)
+    }
+
+    fn enter_insert(&mut self, out: &mut io::Stdout, state: &mut State) -> promkit::Result<()> {
+        self.active = false;
+        let old = state.1.label.width();
+        if let Some(label) = self.label.take() {
+            state.1.label = label;
+        }
+        Self::relabel(out, state, old)
+    }
+
+    // a line was entered, the next one starts in insert mode
+    pub(crate) fn reset(&mut self, state: &mut State) {
+        if let Some(label) = self.label.take() {
+            state.1.label = label;
+        }
+        self.active = false;
+        self.pending = false;
+    }
+
+    pub(crate) fn key(
+        &mut self,
+        ev: &Event,
+        out: &mut io::Stdout,
+        state: &mut State,
+    ) -> promkit::Result<Step> {
+        let c = match ev {
+            Event::Key(KeyEvent {
+                code: KeyCode::Char(c),
+                modifiers: KeyModifiers::NONE | KeyModifiers::SHIFT,
+            }) => *c,
+            _ => return Ok(Step::Pass),
+        };
+        if std::mem::take(&mut self.pending) {
+            delete(&mut state.0.editor, c, &self.chars);
+            return Ok(Step::Done);
+        }
+        let to_tail = |out: &mut io::Stdout, state: &mut State, n: usize| {
+            let editor = &state.0.editor;
+            let n = n.min(editor.data.len() - editor.position());
+            let width = editor.data[editor.position()..editor.position() + n]
+                .iter()
+                .map(|g| g.width)
+                .sum::<usize>();
+            editor.position.set(editor.position() + n);
+            termutil::move_right(out, width as u16)
+        };
+        match c {
+            'd' => self.pending = true,
+            '/' => return Ok(Step::Search),
+            'i' => self.enter_insert(out, state)?,
+            'a' => {
+                to_tail(out, state, 1)?;
+                self.enter_insert(out, state)?;
+            }
+            'A' => {
+                to_tail(out, state, usize::MAX)?;
+                self.enter_insert(out, state)?;
+            }
+            'I' => {
+                termutil::move_left(out, state.0.editor.width_to_position() as u16)?;
+                state.0.editor.to_head();
+                self.enter_insert(out, state)?;
+            }
+            // `$` comes with Shift on most terminals
+            c => {
+                let ev = key(KeyCode::Char(c), KeyModifiers::NONE);
+                self.normal.handle(ev, out, state)?;
+            }
+        }
+        Ok(Step::Done)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn buffer(text: &str, at: usize) -> Buffer {
+        let b = Buffer {
+            data: Graphemes::from(text),
+            ..Default::default()
+        };
+        b.position.set(at);
+        b
+    }
+
+    fn moved(c: char, text: &str, at: usize) -> usize {
+        let (_, to) = motions().into_iter().find(|(k, _)| *k == c).unwrap();
+        to(&Graphemes::from(text), at, edit::WORD_CHARS)
+    }
+
+    #[test]
+    fn motions_stay_on_the_line() {
+        assert_eq!(moved('h', "ab", 0), 0);
+        assert_eq!(moved('l', "ab", 2), 2);
+        assert_eq!(moved('w', "git commit", 1), 4);
+        assert_eq!(moved('b', "git commit", 6), 4);
+        assert_eq!((moved('0', "ab", 1), moved('$', "ab", 1)), (0, 2));
+    }
+
+    #[test]
+    fn dw_and_dd_cut() {
+        let mut b = buffer("git commit -m", 0);
+        delete(&mut b, 'w', edit::WORD_CHARS);
+        assert_eq!(b.data.to_string(), "commit -m");
+        delete(&mut b, 'x', edit::WORD_CHARS);
+        assert_eq!(b.data.to_string(), "commit -m");
+        delete(&mut b, 'd', edit::WORD_CHARS);
+        assert!(b.data.is_empty());
+    }
+}